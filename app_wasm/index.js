@@ -8,11 +8,22 @@ wasm.then((wasm) => {
 
   // pass it into the audio context
   const track = context.createMediaElementSource(audioElement);
-  const granulatorProcessor = context.createScriptProcessor(512, 2, 2);
-  const granulator = new wasm.Granulator();
+  // Channels 0 - 1 carry the track, 2 - 3 the microphone sidechain.
+  const inputMerger = context.createChannelMerger(4);
+  const connectChannels = (source, firstChannel) => {
+    const splitter = context.createChannelSplitter(2);
+    source.connect(splitter);
+    splitter.connect(inputMerger, 0, firstChannel);
+    splitter.connect(inputMerger, 1, firstChannel + 1);
+  };
+  connectChannels(track, 0);
+  let sidechainSource = null;
+  const granulatorProcessor = context.createScriptProcessor(512, 4, 2);
+  const granulator = new wasm.Granulator(context.sampleRate);
+  const envelopeMeter = document.getElementById("envelope");
   granulator.set_new_grain_hook();
 
-  track.connect(granulatorProcessor);
+  inputMerger.connect(granulatorProcessor);
   granulatorProcessor.connect(context.destination);
 
   context.suspend();
@@ -20,14 +31,48 @@ wasm.then((wasm) => {
   granulatorProcessor.onaudioprocess = function (event) {
     const input = event.inputBuffer;
     const output = event.outputBuffer;
-    granulator.process(
-      input.getChannelData(0),
-      input.getChannelData(1),
-      output.getChannelData(0),
-      output.getChannelData(1)
-    );
+    if (sidechainSource) {
+      granulator.process_with_sidechain(
+        input.getChannelData(0),
+        input.getChannelData(1),
+        input.getChannelData(2),
+        input.getChannelData(3),
+        output.getChannelData(0),
+        output.getChannelData(1)
+      );
+    } else {
+      granulator.process(
+        input.getChannelData(0),
+        input.getChannelData(1),
+        output.getChannelData(0),
+        output.getChannelData(1)
+      );
+    }
+    envelopeMeter.value = granulator.envelope();
   };
 
+  const sidechainCheckbox = document.getElementById("sidechain");
+  sidechainCheckbox.addEventListener("change", async function () {
+    const value = sidechainCheckbox.checked;
+    console.log("sidechain", value);
+    if (value && !sidechainSource) {
+      const stream = await navigator.mediaDevices.getUserMedia({ audio: true });
+      sidechainSource = context.createMediaStreamSource(stream);
+      connectChannels(sidechainSource, 2);
+    } else if (!value && sidechainSource) {
+      sidechainSource.mediaStream.getTracks().forEach((track) => track.stop());
+      sidechainSource.disconnect();
+      sidechainSource = null;
+    }
+  });
+
+  const modeSelect = document.getElementById("mode");
+  modeSelect.addEventListener("change", function () {
+    const value = modeSelect.value;
+    console.log("mode", value);
+    granulator.set_mode(wasm.Mode[value]);
+  });
+
   const densitySlider = document.getElementById("density");
   densitySlider.value = granulatorProcessor.density;
   densitySlider.addEventListener("input", function () {
@@ -36,6 +81,68 @@ wasm.then((wasm) => {
     granulator.set_density(value);
   });
 
+  const overlapSlider = document.getElementById("overlap");
+  overlapSlider.addEventListener("input", function () {
+    const value = parseFloat(overlapSlider.value);
+    console.log("overlap", value);
+    granulator.set_overlap(value > 0 ? value : undefined);
+  });
+
+  const grainTriggerSelect = document.getElementById("grain-trigger");
+  grainTriggerSelect.addEventListener("change", function () {
+    const value = grainTriggerSelect.value;
+    console.log("grain-trigger", value);
+    granulator.set_grain_trigger(wasm.GrainTrigger[value]);
+  });
+
+  const tempoSlider = document.getElementById("tempo");
+  tempoSlider.addEventListener("input", function () {
+    const value = parseFloat(tempoSlider.value);
+    console.log("tempo", value);
+    granulator.set_tempo(value);
+  });
+
+  const patternDivisionSlider = document.getElementById("pattern-division");
+  patternDivisionSlider.addEventListener("input", function () {
+    const value = parseInt(patternDivisionSlider.value);
+    console.log("pattern-division", value);
+    granulator.set_pattern_division(value);
+  });
+
+  const patternSliders = ["pattern-hits", "pattern-steps", "pattern-rotation"].map(
+    (id) => document.getElementById(id)
+  );
+  patternSliders.forEach((slider) =>
+    slider.addEventListener("input", function () {
+      const [hits, steps, rotation] = patternSliders.map((slider) =>
+        parseInt(slider.value)
+      );
+      console.log("pattern", hits, steps, rotation);
+      granulator.set_euclidean_pattern(hits, steps, rotation);
+    })
+  );
+
+  const onsetSensitivitySlider = document.getElementById("onset-sensitivity");
+  onsetSensitivitySlider.addEventListener("input", function () {
+    const value = parseFloat(onsetSensitivitySlider.value);
+    console.log("onset-sensitivity", value);
+    granulator.set_onset_sensitivity(value);
+  });
+
+  const onsetRefractorySlider = document.getElementById("onset-refractory");
+  onsetRefractorySlider.addEventListener("input", function () {
+    const value = parseFloat(onsetRefractorySlider.value);
+    console.log("onset-refractory", value);
+    granulator.set_onset_refractory(value);
+  });
+
+  const onsetBurstSlider = document.getElementById("onset-burst");
+  onsetBurstSlider.addEventListener("input", function () {
+    const value = parseInt(onsetBurstSlider.value);
+    console.log("onset-burst", value);
+    granulator.set_onset_burst(value);
+  });
+
   const volumeSlider = document.getElementById("volume");
   volumeSlider.value = granulatorProcessor.volume;
   volumeSlider.addEventListener("input", function () {
@@ -49,15 +156,15 @@ wasm.then((wasm) => {
   positionsSlider.addEventListener("input", function () {
     const value = parseFloat(positionsSlider.value);
     console.log("position", value);
-    granulator.set_position(value);
+    granulator.set_position_milliseconds(value);
   });
 
   const durationSlider = document.getElementById("duration");
   densitySlider.value = granulatorProcessor.duration;
   durationSlider.addEventListener("input", function () {
-    const value = parseInt(durationSlider.value);
+    const value = parseFloat(durationSlider.value);
     console.log("duration", value);
-    granulator.set_duration(value);
+    granulator.set_duration_milliseconds(value);
   });
 
   const pitchSlider = document.getElementById("pitch");
@@ -65,7 +172,7 @@ wasm.then((wasm) => {
   pitchSlider.addEventListener("input", function () {
     const value = parseFloat(pitchSlider.value);
     console.log("pitch", value);
-    granulator.set_pitch(value);
+    granulator.set_pitch_semitones(value, 0);
   });
 
   const feedbackSlider = document.getElementById("feedback");
@@ -84,6 +191,214 @@ wasm.then((wasm) => {
     granulator.set_wet_dry(value);
   });
 
+  const crossfadeLawSelect = document.getElementById("crossfade-law");
+  crossfadeLawSelect.addEventListener("change", function () {
+    const value = crossfadeLawSelect.value;
+    console.log("crossfade-law", value);
+    granulator.set_crossfade_law(wasm.CrossfadeLaw[value]);
+  });
+
+  const dryLevelSlider = document.getElementById("dry-level");
+  dryLevelSlider.addEventListener("input", function () {
+    const value = parseFloat(dryLevelSlider.value);
+    console.log("dry-level", value);
+    granulator.set_dry_level(value);
+  });
+
+  const wetLevelSlider = document.getElementById("wet-level");
+  wetLevelSlider.addEventListener("input", function () {
+    const value = parseFloat(wetLevelSlider.value);
+    console.log("wet-level", value);
+    granulator.set_wet_level(value);
+  });
+
+  const grainSpreadSlider = document.getElementById("grain-spread");
+  grainSpreadSlider.addEventListener("input", function () {
+    const value = parseFloat(grainSpreadSlider.value);
+    console.log("grain-spread", value);
+    granulator.set_grain_spread(value);
+  });
+
+  const zeroCrossingWindowSlider = document.getElementById("zero-crossing-window");
+  zeroCrossingWindowSlider.addEventListener("input", function () {
+    const value = parseFloat(zeroCrossingWindowSlider.value);
+    console.log("zero-crossing-window", value);
+    granulator.set_zero_crossing_window(value);
+  });
+
+  const grainFilterSelect = document.getElementById("grain-filter");
+  grainFilterSelect.addEventListener("change", function () {
+    const value = grainFilterSelect.value;
+    console.log("grain-filter", value);
+    granulator.set_grain_filter(wasm.GrainFilter[value]);
+  });
+
+  const grainFilterCutoffSlider = document.getElementById("grain-filter-cutoff");
+  grainFilterCutoffSlider.addEventListener("input", function () {
+    const value = parseFloat(grainFilterCutoffSlider.value);
+    console.log("grain-filter-cutoff", value);
+    granulator.set_grain_filter_cutoff(value);
+  });
+
+  const grainFilterCutoffSpreadSlider = document.getElementById("grain-filter-cutoff-spread");
+  grainFilterCutoffSpreadSlider.addEventListener("input", function () {
+    const value = parseFloat(grainFilterCutoffSpreadSlider.value);
+    console.log("grain-filter-cutoff-spread", value);
+    granulator.set_grain_filter_cutoff_spread(value);
+  });
+
+  const grainFilterResonanceSlider = document.getElementById("grain-filter-resonance");
+  grainFilterResonanceSlider.addEventListener("input", function () {
+    const value = parseFloat(grainFilterResonanceSlider.value);
+    console.log("grain-filter-resonance", value);
+    granulator.set_grain_filter_resonance(value);
+  });
+
+  const grainFilterResonanceSpreadSlider = document.getElementById("grain-filter-resonance-spread");
+  grainFilterResonanceSpreadSlider.addEventListener("input", function () {
+    const value = parseFloat(grainFilterResonanceSpreadSlider.value);
+    console.log("grain-filter-resonance-spread", value);
+    granulator.set_grain_filter_resonance_spread(value);
+  });
+
+  const grainFilterSweepSlider = document.getElementById("grain-filter-sweep");
+  grainFilterSweepSlider.addEventListener("input", function () {
+    const value = parseFloat(grainFilterSweepSlider.value);
+    console.log("grain-filter-sweep", value);
+    granulator.set_grain_filter_sweep(value);
+  });
+
+  // Resizes the grain pool, so only applied once the slider is released.
+  const maxGrainsSlider = document.getElementById("max-grains");
+  maxGrainsSlider.addEventListener("change", function () {
+    const value = parseInt(maxGrainsSlider.value);
+    console.log("max-grains", value);
+    granulator.set_max_grains(value);
+  });
+
+  const diffusionSlider = document.getElementById("diffusion");
+  diffusionSlider.addEventListener("input", function () {
+    const value = parseFloat(diffusionSlider.value);
+    console.log("diffusion", value);
+    granulator.set_diffusion(value);
+  });
+
+  const reverbRoutingSelect = document.getElementById("reverb-routing");
+  reverbRoutingSelect.addEventListener("change", function () {
+    const value = reverbRoutingSelect.value;
+    console.log("reverb-routing", value);
+    granulator.set_reverb_routing(wasm.ReverbRouting[value]);
+  });
+
+  const reverbAmountSlider = document.getElementById("reverb-amount");
+  reverbAmountSlider.addEventListener("input", function () {
+    const value = parseFloat(reverbAmountSlider.value);
+    console.log("reverb-amount", value);
+    granulator.set_reverb_amount(value);
+  });
+
+  const reverbRoomSizeSlider = document.getElementById("reverb-room-size");
+  reverbRoomSizeSlider.addEventListener("input", function () {
+    const value = parseFloat(reverbRoomSizeSlider.value);
+    console.log("reverb-room-size", value);
+    granulator.set_reverb_room_size(value);
+  });
+
+  const reverbDampingSlider = document.getElementById("reverb-damping");
+  reverbDampingSlider.addEventListener("input", function () {
+    const value = parseFloat(reverbDampingSlider.value);
+    console.log("reverb-damping", value);
+    granulator.set_reverb_damping(value);
+  });
+
+  const reverbWidthSlider = document.getElementById("reverb-width");
+  reverbWidthSlider.addEventListener("input", function () {
+    const value = parseFloat(reverbWidthSlider.value);
+    console.log("reverb-width", value);
+    granulator.set_reverb_width(value);
+  });
+
+  const reverbFreezeCheckbox = document.getElementById("reverb-freeze");
+  reverbFreezeCheckbox.addEventListener("change", function () {
+    const value = reverbFreezeCheckbox.checked;
+    console.log("reverb-freeze", value);
+    granulator.set_reverb_freeze(value);
+  });
+
+  const spectralFreezeCheckbox = document.getElementById("spectral-freeze");
+  spectralFreezeCheckbox.addEventListener("change", function () {
+    const value = spectralFreezeCheckbox.checked;
+    console.log("spectral-freeze", value);
+    granulator.set_spectral_freeze(value);
+  });
+
+  const spectralPhaseRandomizationSlider = document.getElementById(
+    "spectral-phase-randomization"
+  );
+  spectralPhaseRandomizationSlider.addEventListener("input", function () {
+    const value = parseFloat(spectralPhaseRandomizationSlider.value);
+    console.log("spectral-phase-randomization", value);
+    granulator.set_spectral_phase_randomization(value);
+  });
+
+  const spectralQuantizationSlider = document.getElementById(
+    "spectral-quantization"
+  );
+  spectralQuantizationSlider.addEventListener("input", function () {
+    const value = parseFloat(spectralQuantizationSlider.value);
+    console.log("spectral-quantization", value);
+    granulator.set_spectral_quantization(value);
+  });
+
+  const envelopeAttackSlider = document.getElementById("envelope-attack");
+  envelopeAttackSlider.addEventListener("input", function () {
+    const value = parseFloat(envelopeAttackSlider.value);
+    console.log("envelope-attack", value);
+    granulator.set_envelope_attack(value);
+  });
+
+  const envelopeReleaseSlider = document.getElementById("envelope-release");
+  envelopeReleaseSlider.addEventListener("input", function () {
+    const value = parseFloat(envelopeReleaseSlider.value);
+    console.log("envelope-release", value);
+    granulator.set_envelope_release(value);
+  });
+
+  const envelopeGainSlider = document.getElementById("envelope-gain");
+  envelopeGainSlider.addEventListener("input", function () {
+    const value = parseFloat(envelopeGainSlider.value);
+    console.log("envelope-gain", value);
+    granulator.set_envelope_gain(value);
+  });
+
+  const envelopeToDensitySlider = document.getElementById("envelope-to-density");
+  envelopeToDensitySlider.addEventListener("input", function () {
+    const value = parseFloat(envelopeToDensitySlider.value);
+    console.log("envelope-to-density", value);
+    granulator.set_envelope_to_density(value);
+  });
+
+  const envelopeToVolumeSlider = document.getElementById("envelope-to-volume");
+  envelopeToVolumeSlider.addEventListener("input", function () {
+    const value = parseFloat(envelopeToVolumeSlider.value);
+    console.log("envelope-to-volume", value);
+    granulator.set_envelope_to_volume(value);
+  });
+
+  const envelopeToFeedbackSlider = document.getElementById("envelope-to-feedback");
+  envelopeToFeedbackSlider.addEventListener("input", function () {
+    const value = parseFloat(envelopeToFeedbackSlider.value);
+    console.log("envelope-to-feedback", value);
+    granulator.set_envelope_to_feedback(value);
+  });
+
+  const envelopeToPositionSlider = document.getElementById("envelope-to-position");
+  envelopeToPositionSlider.addEventListener("input", function () {
+    const value = parseFloat(envelopeToPositionSlider.value);
+    console.log("envelope-to-position", value);
+    granulator.set_envelope_to_position(value);
+  });
+
   // select our play button
   const playButton = document.querySelector("button");
 
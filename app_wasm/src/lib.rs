@@ -1,3 +1,6 @@
+use granulator::{
+    GrainEnvelope, GrainParameters, GranulatorOptions, Morph, ParameterId, Pattern, Preset,
+};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(module = "/canvas.js")]
@@ -5,6 +8,141 @@ extern "C" {
     fn addGrain(duration: JsValue);
 }
 
+#[wasm_bindgen]
+#[derive(Copy, Clone)]
+pub enum Mode {
+    Cloud,
+    Loop,
+    Spectral,
+}
+
+impl From<Mode> for granulator::Mode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Cloud => granulator::Mode::Cloud,
+            Mode::Loop => granulator::Mode::Loop,
+            Mode::Spectral => granulator::Mode::Spectral,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone)]
+pub enum GrainTrigger {
+    Scheduler,
+    SchedulerAndOnsets,
+    Onsets,
+    Pattern,
+}
+
+impl From<GrainTrigger> for granulator::GrainTrigger {
+    fn from(grain_trigger: GrainTrigger) -> Self {
+        match grain_trigger {
+            GrainTrigger::Scheduler => granulator::GrainTrigger::Scheduler,
+            GrainTrigger::SchedulerAndOnsets => granulator::GrainTrigger::SchedulerAndOnsets,
+            GrainTrigger::Onsets => granulator::GrainTrigger::Onsets,
+            GrainTrigger::Pattern => granulator::GrainTrigger::Pattern,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone)]
+pub enum GrainFilter {
+    Off,
+    Lowpass,
+    Bandpass,
+    Highpass,
+}
+
+impl From<GrainFilter> for granulator::GrainFilter {
+    fn from(grain_filter: GrainFilter) -> Self {
+        match grain_filter {
+            GrainFilter::Off => granulator::GrainFilter::Off,
+            GrainFilter::Lowpass => granulator::GrainFilter::Lowpass,
+            GrainFilter::Bandpass => granulator::GrainFilter::Bandpass,
+            GrainFilter::Highpass => granulator::GrainFilter::Highpass,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone)]
+pub enum ReverbRouting {
+    Bypass,
+    Grains,
+    Output,
+    Feedback,
+}
+
+impl From<ReverbRouting> for granulator::ReverbRouting {
+    fn from(reverb_routing: ReverbRouting) -> Self {
+        match reverb_routing {
+            ReverbRouting::Bypass => granulator::ReverbRouting::Bypass,
+            ReverbRouting::Grains => granulator::ReverbRouting::Grains,
+            ReverbRouting::Output => granulator::ReverbRouting::Output,
+            ReverbRouting::Feedback => granulator::ReverbRouting::Feedback,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone)]
+pub enum CrossfadeLaw {
+    Linear,
+    EqualPower,
+}
+
+impl From<CrossfadeLaw> for granulator::CrossfadeLaw {
+    fn from(crossfade_law: CrossfadeLaw) -> Self {
+        match crossfade_law {
+            CrossfadeLaw::Linear => granulator::CrossfadeLaw::Linear,
+            CrossfadeLaw::EqualPower => granulator::CrossfadeLaw::EqualPower,
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn parameter_count() -> usize {
+    granulator::NUM_PARAMETERS
+}
+
+#[wasm_bindgen]
+pub fn parameter_name(index: usize) -> Option<String> {
+    ParameterId::from_index(index).map(|id| id.descriptor().name.to_string())
+}
+
+#[wasm_bindgen]
+pub fn parameter_min(index: usize) -> Option<f32> {
+    ParameterId::from_index(index).map(|id| id.descriptor().min)
+}
+
+#[wasm_bindgen]
+pub fn parameter_max(index: usize) -> Option<f32> {
+    ParameterId::from_index(index).map(|id| id.descriptor().max)
+}
+
+#[wasm_bindgen]
+pub fn parameter_default(index: usize) -> Option<f32> {
+    ParameterId::from_index(index).map(|id| id.descriptor().default)
+}
+
+#[wasm_bindgen]
+pub fn parameter_unit(index: usize) -> Option<String> {
+    ParameterId::from_index(index).map(|id| id.descriptor().unit.symbol().to_string())
+}
+
+#[wasm_bindgen]
+pub fn format_parameter(index: usize, plain: f32) -> Option<String> {
+    ParameterId::from_index(index).map(|id| id.descriptor().display(plain))
+}
+
+fn parse_preset(json: &str, sample_rate: usize) -> Result<GranulatorOptions, JsValue> {
+    Preset::from_json(json)
+        .map(|preset| preset.options_at(sample_rate))
+        .map_err(|error| JsValue::from(error.to_string()))
+}
+
 #[wasm_bindgen]
 pub struct Granulator(granulator::Granulator);
 
@@ -18,10 +156,19 @@ impl Default for Granulator {
 
 #[wasm_bindgen]
 impl Granulator {
+    /**
+     * `sample_rate` of the audio context, the default sample rate if omitted.
+     */
     #[wasm_bindgen(constructor)]
-    pub fn new() -> Self {
+    pub fn new(sample_rate: Option<u32>) -> Self {
         console_error_panic_hook::set_once();
-        Self::default()
+        match sample_rate {
+            Some(sample_rate) => Self(granulator::Granulator::with_sample_rate(
+                granulator::GranulatorOptions::for_sample_rate(sample_rate as usize),
+                sample_rate as usize,
+            )),
+            None => Self::default(),
+        }
     }
 
     pub fn process(
@@ -38,10 +185,184 @@ impl Granulator {
         }
     }
 
+    /**
+     * Grains are read from the sidechain, the input forms the dry signal
+     * and drives onsets and the envelope follower.
+     */
+    pub fn process_with_sidechain(
+        &mut self,
+        input_l: &[f32],
+        input_r: &[f32],
+        sidechain_l: &[f32],
+        sidechain_r: &[f32],
+        output_l: &mut [f32],
+        output_r: &mut [f32],
+    ) {
+        for i in 0..input_l.len() {
+            let out = self
+                .0
+                .process_with_sidechain([input_l[i], input_r[i]], [sidechain_l[i], sidechain_r[i]]);
+            output_l[i] = out[0];
+            output_r[i] = out[1];
+        }
+    }
+
+    /**
+     * Starts a grain `offset` samples into the next block, with position and
+     * duration in samples. Returns false when too many grains are waiting.
+     */
+    pub fn trigger_grain(
+        &mut self,
+        position: f32,
+        duration: f32,
+        pitch: f32,
+        pan: f32,
+        amplitude: f32,
+        offset: usize,
+    ) -> bool {
+        let parameters = GrainParameters {
+            position,
+            duration,
+            pitch,
+            pan,
+            amplitude,
+            envelope: GrainEnvelope::Parabolic,
+        };
+        self.0.trigger_grain(parameters, offset)
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset()
+    }
+
+    pub fn set_parameter(&mut self, index: usize, plain: f32) {
+        if let Some(id) = ParameterId::from_index(index) {
+            self.0.set_parameter(id, plain)
+        }
+    }
+
+    pub fn set_parameter_normalized(&mut self, index: usize, normalized: f32) {
+        if let Some(id) = ParameterId::from_index(index) {
+            self.0.set_parameter_normalized(id, normalized)
+        }
+    }
+
+    pub fn get_parameter(&self, index: usize) -> Option<f32> {
+        ParameterId::from_index(index).map(|id| self.0.get_parameter(id))
+    }
+
+    pub fn get_parameter_normalized(&self, index: usize) -> Option<f32> {
+        ParameterId::from_index(index).map(|id| self.0.get_parameter_normalized(id))
+    }
+
+    /**
+     * Loads a JSON preset, keeps the current state if it can't be parsed.
+     */
+    pub fn import_preset(&mut self, json: &str) -> Result<(), JsValue> {
+        self.0
+            .set_options(&parse_preset(json, self.0.sample_rate())?);
+        Ok(())
+    }
+
+    pub fn export_preset(&self) -> Result<String, JsValue> {
+        Preset::with_sample_rate(self.0.options(), self.0.sample_rate())
+            .to_json()
+            .map_err(|error| JsValue::from(error.to_string()))
+    }
+
+    /**
+     * Morphs between two JSON presets along x.
+     */
+    pub fn set_morph_crossfade(&mut self, from: &str, to: &str) -> Result<(), JsValue> {
+        let sample_rate = self.0.sample_rate();
+        let from = parse_preset(from, sample_rate)?;
+        let to = parse_preset(to, sample_rate)?;
+        self.0
+            .set_morph(Some(Morph::crossfade(&from, &to, sample_rate)));
+        Ok(())
+    }
+
+    /**
+     * Morphs between four JSON presets at the corners (0, 0), (1, 0), (0, 1), (1, 1).
+     */
+    pub fn set_morph_xy(
+        &mut self,
+        bottom_left: &str,
+        bottom_right: &str,
+        top_left: &str,
+        top_right: &str,
+    ) -> Result<(), JsValue> {
+        let sample_rate = self.0.sample_rate();
+        let corners = [
+            parse_preset(bottom_left, sample_rate)?,
+            parse_preset(bottom_right, sample_rate)?,
+            parse_preset(top_left, sample_rate)?,
+            parse_preset(top_right, sample_rate)?,
+        ];
+        self.0.set_morph(Some(Morph::xy(
+            [&corners[0], &corners[1], &corners[2], &corners[3]],
+            sample_rate,
+        )));
+        Ok(())
+    }
+
+    pub fn clear_morph(&mut self) {
+        self.0.set_morph(None)
+    }
+
+    pub fn set_morph_position(&mut self, x: f32, y: f32) {
+        self.0.set_morph_position(x, y)
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.0.set_mode(mode.into())
+    }
+
     pub fn set_density(&mut self, denisity: f32) {
         self.0.set_density(denisity)
     }
 
+    /**
+     * Average number of overlapping grains, `undefined` goes back to density.
+     */
+    pub fn set_overlap(&mut self, overlap: Option<f32>) {
+        self.0.set_overlap(overlap)
+    }
+
+    pub fn set_grain_trigger(&mut self, grain_trigger: GrainTrigger) {
+        self.0.set_grain_trigger(grain_trigger.into())
+    }
+
+    pub fn set_onset_sensitivity(&mut self, sensitivity: f32) {
+        self.0.set_onset_sensitivity(sensitivity)
+    }
+
+    pub fn set_onset_refractory(&mut self, milliseconds: f32) {
+        self.0.set_onset_refractory(milliseconds)
+    }
+
+    pub fn set_onset_burst(&mut self, num_grains: usize) {
+        self.0.set_onset_burst(num_grains)
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.0.set_tempo(tempo)
+    }
+
+    pub fn set_pattern_division(&mut self, division: usize) {
+        self.0.set_pattern_division(division)
+    }
+
+    pub fn set_euclidean_pattern(&mut self, hits: usize, num_steps: usize, rotation: usize) {
+        self.0
+            .set_pattern(Pattern::euclidean(hits, num_steps, rotation))
+    }
+
+    pub fn set_probability_pattern(&mut self, probabilities: &[f32]) {
+        self.0
+            .set_pattern(Pattern::from_probabilities(probabilities))
+    }
+
     pub fn set_volume(&mut self, volume: f32) {
         self.0.set_volume(volume)
     }
@@ -50,18 +371,126 @@ impl Granulator {
         self.0.set_wet_dry(wet_dry)
     }
 
+    pub fn set_crossfade_law(&mut self, crossfade_law: CrossfadeLaw) {
+        self.0.set_crossfade_law(crossfade_law.into())
+    }
+
+    pub fn set_dry_level(&mut self, dry_level: f32) {
+        self.0.set_dry_level(dry_level)
+    }
+
+    pub fn set_wet_level(&mut self, wet_level: f32) {
+        self.0.set_wet_level(wet_level)
+    }
+
+    pub fn set_dry_latency_compensation(&mut self, is_enabled: bool) {
+        self.0.set_dry_latency_compensation(is_enabled)
+    }
+
+    pub fn set_grain_spread(&mut self, grain_spread: f32) {
+        self.0.set_grain_spread(grain_spread)
+    }
+
+    pub fn set_zero_crossing_window(&mut self, milliseconds: f32) {
+        self.0.set_zero_crossing_window(milliseconds)
+    }
+
+    pub fn set_grain_filter(&mut self, grain_filter: GrainFilter) {
+        self.0.set_grain_filter(grain_filter.into())
+    }
+
+    pub fn set_grain_filter_cutoff(&mut self, cutoff: f32) {
+        self.0.set_grain_filter_cutoff(cutoff)
+    }
+
+    pub fn set_grain_filter_cutoff_spread(&mut self, octaves: f32) {
+        self.0.set_grain_filter_cutoff_spread(octaves)
+    }
+
+    pub fn set_grain_filter_resonance(&mut self, resonance: f32) {
+        self.0.set_grain_filter_resonance(resonance)
+    }
+
+    pub fn set_grain_filter_resonance_spread(&mut self, spread: f32) {
+        self.0.set_grain_filter_resonance_spread(spread)
+    }
+
+    pub fn set_grain_filter_sweep(&mut self, octaves: f32) {
+        self.0.set_grain_filter_sweep(octaves)
+    }
+
+    pub fn set_max_grains(&mut self, max_grains: usize) {
+        self.0.set_max_grains(max_grains)
+    }
+
+    pub fn set_diffusion(&mut self, diffusion: f32) {
+        self.0.set_diffusion(diffusion)
+    }
+
+    pub fn set_reverb_routing(&mut self, reverb_routing: ReverbRouting) {
+        self.0.set_reverb_routing(reverb_routing.into())
+    }
+
+    pub fn set_reverb_amount(&mut self, reverb_amount: f32) {
+        self.0.set_reverb_amount(reverb_amount)
+    }
+
+    pub fn set_reverb_room_size(&mut self, room_size: f32) {
+        self.0.set_reverb_room_size(room_size)
+    }
+
+    pub fn set_reverb_damping(&mut self, damping: f32) {
+        self.0.set_reverb_damping(damping)
+    }
+
+    pub fn set_reverb_width(&mut self, width: f32) {
+        self.0.set_reverb_width(width)
+    }
+
+    pub fn set_reverb_freeze(&mut self, is_frozen: bool) {
+        self.0.set_reverb_freeze(is_frozen)
+    }
+
     pub fn set_feedback(&mut self, feedback: f32) {
         self.0.set_feedback(feedback)
     }
 
+    pub fn set_feedback_high_pass(&mut self, cutoff: Option<f32>) {
+        self.0.set_feedback_high_pass(cutoff)
+    }
+
+    pub fn set_feedback_low_pass(&mut self, cutoff: Option<f32>) {
+        self.0.set_feedback_low_pass(cutoff)
+    }
+
+    pub fn set_feedback_dc_blocker(&mut self, is_enabled: bool) {
+        self.0.set_feedback_dc_blocker(is_enabled)
+    }
+
+    pub fn set_feedback_saturation(&mut self, drive: Option<f32>) {
+        self.0.set_feedback_saturation(drive)
+    }
+
     pub fn set_position(&mut self, position: f32) {
         self.0.set_position(position)
     }
 
+    pub fn set_position_milliseconds(&mut self, milliseconds: f32) {
+        self.0.set_position_milliseconds(milliseconds)
+    }
+
+    pub fn set_position_fraction(&mut self, fraction: f32) {
+        self.0.set_position_fraction(fraction)
+    }
+
     pub fn set_duration(&mut self, duration: f32) {
         self.0.set_duration(duration)
     }
 
+    pub fn set_duration_milliseconds(&mut self, milliseconds: f32) {
+        self.0.set_duration_milliseconds(milliseconds)
+    }
+
     pub fn set_new_grain_hook(&mut self) {
         let hook = |duration: f32| unsafe {
             let js_duration: JsValue = duration.into();
@@ -73,4 +502,56 @@ impl Granulator {
     pub fn set_pitch(&mut self, pitch: f32) {
         self.0.set_pitch(pitch)
     }
+
+    pub fn set_pitch_semitones(&mut self, semitones: f32, cents: f32) {
+        self.0.set_pitch_semitones(semitones, cents)
+    }
+
+    pub fn set_pitch_volts(&mut self, volts: f32) {
+        self.0.set_pitch_volts(volts)
+    }
+
+    pub fn set_spectral_freeze(&mut self, is_frozen: bool) {
+        self.0.set_spectral_freeze(is_frozen)
+    }
+
+    pub fn set_spectral_phase_randomization(&mut self, phase_randomization: f32) {
+        self.0.set_spectral_phase_randomization(phase_randomization)
+    }
+
+    pub fn set_spectral_quantization(&mut self, quantization: f32) {
+        self.0.set_spectral_quantization(quantization)
+    }
+
+    pub fn set_envelope_attack(&mut self, milliseconds: f32) {
+        self.0.set_envelope_attack(milliseconds)
+    }
+
+    pub fn set_envelope_release(&mut self, milliseconds: f32) {
+        self.0.set_envelope_release(milliseconds)
+    }
+
+    pub fn set_envelope_gain(&mut self, decibels: f32) {
+        self.0.set_envelope_gain(decibels)
+    }
+
+    pub fn set_envelope_to_density(&mut self, depth: f32) {
+        self.0.set_envelope_to_density(depth)
+    }
+
+    pub fn set_envelope_to_volume(&mut self, depth: f32) {
+        self.0.set_envelope_to_volume(depth)
+    }
+
+    pub fn set_envelope_to_feedback(&mut self, depth: f32) {
+        self.0.set_envelope_to_feedback(depth)
+    }
+
+    pub fn set_envelope_to_position(&mut self, depth: f32) {
+        self.0.set_envelope_to_position(depth)
+    }
+
+    pub fn envelope(&self) -> f32 {
+        self.0.envelope()
+    }
 }
@@ -1,4 +1,4 @@
-use granulator::{Granulator, GranulatorOptions};
+use granulator::{Granulator, GranulatorOptions, Preset};
 use rand::Rng;
 
 use cpal;
@@ -45,10 +45,13 @@ fn main() -> Result<(), anyhow::Error> {
     // A channel for indicating when playback has completed.
     let (complete_tx, complete_rx) = std::sync::mpsc::sync_channel(1);
     let _delay_time_seconds: usize = 2;
-    let options = GranulatorOptions {
-        ..GranulatorOptions::default()
+    let options = match preset_path() {
+        Some(path) => Preset::from_toml(&std::fs::read_to_string(path)?)?
+            .options_at(spec.sample_rate as usize),
+        None => GranulatorOptions::for_sample_rate(spec.sample_rate as usize),
     };
-    let mut granulator = Granulator::new(options);
+    let mut granulator: Granulator =
+        Granulator::with_sample_rate(options, spec.sample_rate as usize);
     //granulator.set_new_grain_hook(Some(|duration| println!("duration = {}\n", duration)));
 
     let mut counter = 0;
@@ -95,3 +98,12 @@ fn main() -> Result<(), anyhow::Error> {
     stream.pause().ok();
     Ok(())
 }
+
+/**
+ * Path given with `--preset file.toml`, if any.
+ */
+fn preset_path() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--preset");
+    args.next()?;
+    args.next()
+}
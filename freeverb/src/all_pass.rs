@@ -1,25 +1,64 @@
+use num_traits::Float;
+
+use crate::constant;
 use crate::delay_line::DelayLine;
 
-pub struct AllPass {
-    delay_line: DelayLine,
+pub struct AllPass<T = f32> {
+    delay_line: DelayLine<T>,
+    feedback: T,
+}
+
+impl<T: Float> Clone for AllPass<T> {
+    fn clone(&self) -> Self {
+        Self {
+            delay_line: self.delay_line.clone(),
+            feedback: self.feedback,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.delay_line.clone_from(&source.delay_line);
+        self.feedback = source.feedback;
+    }
 }
 
-impl AllPass {
+impl<T: Float> AllPass<T> {
     pub fn new(delay_length: usize) -> Self {
         Self {
             delay_line: DelayLine::new(delay_length),
+            // in the original version of freeverb this is never modified
+            feedback: constant(0.5),
         }
     }
 
-    pub fn tick(&mut self, input: f32) -> f32 {
+    pub fn set_feedback(&mut self, value: T) {
+        self.feedback = value;
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+    }
+
+    pub fn tick(&mut self, input: T) -> T {
         let delayed = self.delay_line.read();
-        let output = -input + delayed;
+        self.tick_delayed(input, delayed)
+    }
+
+    /**
+     * Same as `tick`, with the delay shortened by a fractional number of samples,
+     * which allows modulating the delay length. The offset is expected
+     * to be in the range [0, delay_length - 1].
+     */
+    pub fn tick_modulated(&mut self, input: T, offset: T) -> T {
+        let delayed = self.delay_line.read_with_offset(offset);
+        self.tick_delayed(input, delayed)
+    }
 
-        // in the original version of freeverb this is a member which is never modified
-        let feedback = 0.5;
+    fn tick_delayed(&mut self, input: T, delayed: T) -> T {
+        let output = -input + delayed;
 
         self.delay_line
-            .write_and_advance(input + delayed * feedback);
+            .write_and_advance(input + delayed * self.feedback);
 
         output
     }
@@ -38,4 +77,13 @@ mod tests {
         assert_eq!(allpass.tick(0.0), 0.0);
         assert_eq!(allpass.tick(0.0), 0.25);
     }
+
+    #[test]
+    fn configurable_feedback() {
+        let mut allpass = super::AllPass::new(1);
+        allpass.set_feedback(0.25);
+        assert_eq!(allpass.tick(1.0), -1.0);
+        assert_eq!(allpass.tick(0.0), 1.0);
+        assert_eq!(allpass.tick(0.0), 0.25);
+    }
 }
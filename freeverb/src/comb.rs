@@ -1,34 +1,59 @@
+use num_traits::Float;
+
+use crate::constant;
 use crate::delay_line::DelayLine;
 
-pub struct Comb {
-    delay_line: DelayLine,
-    feedback: f32,
-    filter_state: f32,
-    dampening: f32,
-    dampening_inverse: f32,
+pub struct Comb<T = f32> {
+    delay_line: DelayLine<T>,
+    feedback: T,
+    filter_state: T,
+    dampening: T,
+    dampening_inverse: T,
+}
+
+impl<T: Float> Clone for Comb<T> {
+    fn clone(&self) -> Self {
+        Self {
+            delay_line: self.delay_line.clone(),
+            ..*self
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.delay_line.clone_from(&source.delay_line);
+        self.feedback = source.feedback;
+        self.filter_state = source.filter_state;
+        self.dampening = source.dampening;
+        self.dampening_inverse = source.dampening_inverse;
+    }
 }
 
-impl Comb {
+impl<T: Float> Comb<T> {
     pub fn new(delay_length: usize) -> Self {
         Self {
             delay_line: DelayLine::new(delay_length),
-            feedback: 0.5,
-            filter_state: 0.0,
-            dampening: 0.5,
-            dampening_inverse: 0.5,
+            feedback: constant(0.5),
+            filter_state: T::zero(),
+            dampening: constant(0.5),
+            dampening_inverse: constant(0.5),
         }
     }
 
-    pub fn set_dampening(&mut self, value: f32) {
+    pub fn set_dampening(&mut self, value: T) {
         self.dampening = value;
-        self.dampening_inverse = 1.0 - value;
+        self.dampening_inverse = T::one() - value;
     }
 
-    pub fn set_feedback(&mut self, value: f32) {
+    pub fn set_feedback(&mut self, value: T) {
         self.feedback = value;
     }
 
-    pub fn tick(&mut self, input: f32) -> f32 {
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.filter_state = T::zero();
+    }
+
+    pub fn tick(&mut self, input: T) -> T {
         let output = self.delay_line.read();
 
         self.filter_state = output * self.dampening_inverse + self.filter_state * self.dampening;
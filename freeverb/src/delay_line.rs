@@ -1,21 +1,60 @@
-pub struct DelayLine {
-    buffer: Vec<f32>,
+use num_traits::Float;
+
+pub struct DelayLine<T = f32> {
+    buffer: Vec<T>,
     index: usize,
 }
 
-impl DelayLine {
+// `clone_from` reuses the buffer, so that restoring a state doesn't allocate.
+impl<T: Float> Clone for DelayLine<T> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            index: self.index,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.buffer.clone_from(&source.buffer);
+        self.index = source.index;
+    }
+}
+
+impl<T: Float> DelayLine<T> {
     pub fn new(length: usize) -> Self {
         Self {
-            buffer: vec![0.0; length],
+            buffer: vec![T::zero(); length],
             index: 0,
         }
     }
 
-    pub fn read(&self) -> f32 {
+    pub fn read(&self) -> T {
         self.buffer[self.index]
     }
 
-    pub fn write_and_advance(&mut self, value: f32) {
+    /**
+     * Reads `offset` samples ahead of the oldest sample, i.e. with a delay of
+     * `length - offset` samples. The offset can be fractional and is expected
+     * to be in the range [0, length - 1].
+     */
+    pub fn read_with_offset(&self, offset: T) -> T {
+        let length = self.buffer.len();
+        let whole = offset.floor();
+        let fraction = offset - whole;
+        let current = (self.index + whole.to_usize().unwrap_or(0)) % length;
+        let next = (current + 1) % length;
+
+        self.buffer[current] + fraction * (self.buffer[next] - self.buffer[current])
+    }
+
+    pub fn reset(&mut self) {
+        for sample in self.buffer.iter_mut() {
+            *sample = T::zero();
+        }
+        self.index = 0;
+    }
+
+    pub fn write_and_advance(&mut self, value: T) {
         self.buffer[self.index] = value;
 
         if self.index == self.buffer.len() - 1 {
@@ -45,6 +84,17 @@ mod tests {
         };
     }
 
+    #[test]
+    fn read_with_offset_interpolates() {
+        let mut line = super::DelayLine::new(4);
+        for i in 0..4 {
+            line.write_and_advance(i as f32);
+        }
+        assert_eq!(line.read_with_offset(0.0), line.read());
+        assert_eq!(line.read_with_offset(1.0), 1.0);
+        assert_eq!(line.read_with_offset(2.5), 2.5);
+    }
+
     delay_line_test!(length_1, 1);
     delay_line_test!(length_3, 3);
     delay_line_test!(length_10, 10);
@@ -1,4 +1,6 @@
-use crate::{all_pass::AllPass, comb::Comb};
+use num_traits::Float;
+
+use crate::{all_pass::AllPass, comb::Comb, constant};
 
 const FIXED_GAIN: f32 = 0.015;
 
@@ -36,16 +38,16 @@ const ALLPASS_TUNING_R3: usize = 341 + STEREO_SPREAD;
 const ALLPASS_TUNING_L4: usize = 225;
 const ALLPASS_TUNING_R4: usize = 225 + STEREO_SPREAD;
 
-pub struct Freeverb {
-    combs: [(Comb, Comb); 8],
-    allpasses: [(AllPass, AllPass); 4],
-    wet_gains: (f32, f32),
-    wet: f32,
-    width: f32,
-    dry: f32,
-    input_gain: f32,
-    dampening: f32,
-    room_size: f32,
+pub struct Freeverb<T = f32> {
+    combs: [(Comb<T>, Comb<T>); 8],
+    allpasses: [(AllPass<T>, AllPass<T>); 4],
+    wet_gains: (T, T),
+    wet: T,
+    width: T,
+    dry: T,
+    input_gain: T,
+    dampening: T,
+    room_size: T,
     frozen: bool,
 }
 
@@ -53,7 +55,37 @@ fn adjust_length(length: usize, sr: usize) -> usize {
     (length as f32 * sr as f32 / 44100.0) as usize
 }
 
-impl Freeverb {
+impl<T: Float> Clone for Freeverb<T> {
+    fn clone(&self) -> Self {
+        Self {
+            combs: self.combs.clone(),
+            allpasses: self.allpasses.clone(),
+            ..*self
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        for (combs, source_combs) in self.combs.iter_mut().zip(source.combs.iter()) {
+            combs.0.clone_from(&source_combs.0);
+            combs.1.clone_from(&source_combs.1);
+        }
+        for (allpasses, source_allpasses) in self.allpasses.iter_mut().zip(source.allpasses.iter())
+        {
+            allpasses.0.clone_from(&source_allpasses.0);
+            allpasses.1.clone_from(&source_allpasses.1);
+        }
+        self.wet_gains = source.wet_gains;
+        self.wet = source.wet;
+        self.width = source.width;
+        self.dry = source.dry;
+        self.input_gain = source.input_gain;
+        self.dampening = source.dampening;
+        self.room_size = source.room_size;
+        self.frozen = source.frozen;
+    }
+}
+
+impl<T: Float> Freeverb<T> {
     pub fn new(sr: usize) -> Self {
         let mut freeverb = Freeverb {
             combs: [
@@ -108,33 +140,33 @@ impl Freeverb {
                     AllPass::new(adjust_length(ALLPASS_TUNING_R4, sr)),
                 ),
             ],
-            wet_gains: (0.0, 0.0),
-            wet: 0.0,
-            dry: 0.0,
-            input_gain: 0.0,
-            width: 0.0,
-            dampening: 0.0,
-            room_size: 0.0,
+            wet_gains: (T::zero(), T::zero()),
+            wet: T::zero(),
+            dry: T::zero(),
+            input_gain: T::zero(),
+            width: T::zero(),
+            dampening: T::zero(),
+            room_size: T::zero(),
             frozen: false,
         };
 
-        freeverb.set_wet(1.0);
-        freeverb.set_width(0.1);
-        freeverb.set_dampening(0.1);
-        freeverb.set_room_size(0.1);
-        freeverb.set_frozen(false);
+        freeverb.set_wet(T::one());
+        freeverb.set_width(constant(0.1));
+        freeverb.set_dampening(constant(0.1));
+        freeverb.set_room_size(constant(0.1));
+        freeverb.set_freeze(false);
 
         freeverb
     }
 
-    pub fn tick(&mut self, input: (f32, f32)) -> (f32, f32) {
-        let input_mixed = (input.0 + input.1) * FIXED_GAIN * self.input_gain;
+    pub fn tick(&mut self, input: (T, T)) -> (T, T) {
+        let input_mixed = (input.0 + input.1) * constant(FIXED_GAIN) * self.input_gain;
 
-        let mut out = (0.0, 0.0);
+        let mut out = (T::zero(), T::zero());
 
         for combs in self.combs.iter_mut() {
-            out.0 += combs.0.tick(input_mixed);
-            out.1 += combs.1.tick(input_mixed);
+            out.0 = out.0 + combs.0.tick(input_mixed);
+            out.1 = out.1 + combs.1.tick(input_mixed);
         }
 
         for allpasses in self.allpasses.iter_mut() {
@@ -148,47 +180,57 @@ impl Freeverb {
         )
     }
 
-    pub fn set_dampening(&mut self, value: f32) {
-        self.dampening = value * SCALE_DAMPENING;
+    /**
+     * Silences the tail, settings are kept.
+     */
+    pub fn reset(&mut self) {
+        for combs in self.combs.iter_mut() {
+            combs.0.reset();
+            combs.1.reset();
+        }
+        for allpasses in self.allpasses.iter_mut() {
+            allpasses.0.reset();
+            allpasses.1.reset();
+        }
+    }
+
+    pub fn set_dampening(&mut self, value: T) {
+        self.dampening = value * constant(SCALE_DAMPENING);
         self.update_combs();
     }
 
     pub fn set_freeze(&mut self, frozen: bool) {
         self.frozen = frozen;
+        self.input_gain = if frozen { T::zero() } else { T::one() };
         self.update_combs();
     }
 
-    pub fn set_wet(&mut self, value: f32) {
-        self.wet = value * SCALE_WET;
+    pub fn set_wet(&mut self, value: T) {
+        self.wet = value * constant(SCALE_WET);
         self.update_wet_gains();
     }
 
-    pub fn set_width(&mut self, value: f32) {
+    pub fn set_width(&mut self, value: T) {
         self.width = value;
         self.update_wet_gains();
     }
 
     fn update_wet_gains(&mut self) {
+        let half = constant::<T>(0.5);
         self.wet_gains = (
-            self.wet * (self.width / 2.0 + 0.5),
-            self.wet * ((1.0 - self.width) / 2.0),
+            self.wet * (self.width * half + half),
+            self.wet * ((T::one() - self.width) * half),
         )
     }
 
-    fn set_frozen(&mut self, frozen: bool) {
-        self.frozen = frozen;
-        self.input_gain = if frozen { 0.0 } else { 1.0 };
-        self.update_combs();
-    }
-
-    pub fn set_room_size(&mut self, value: f32) {
-        self.room_size = value * SCALE_ROOM + OFFSET_ROOM;
+    pub fn set_room_size(&mut self, value: T) {
+        self.room_size = value * constant(SCALE_ROOM) + constant(OFFSET_ROOM);
         self.update_combs();
     }
 
     fn update_combs(&mut self) {
         let (feedback, dampening) = if self.frozen {
-            (1.0, 0.0)
+            (T::one(), T::zero())
         } else {
             (self.room_size, self.dampening)
         };
@@ -202,7 +244,7 @@ impl Freeverb {
         }
     }
 
-    pub fn set_dry(&mut self, value: f32) {
+    pub fn set_dry(&mut self, value: T) {
         self.dry = value;
     }
 }
@@ -211,11 +253,55 @@ impl Freeverb {
 mod tests {
     #[test]
     fn ticking_does_something() {
-        let mut freeverb = super::Freeverb::new(44100);
+        let mut freeverb: super::Freeverb = super::Freeverb::new(44100);
         assert_eq!(freeverb.tick((1.0, 1.0)), (0.0, 0.0));
         for _ in 0..super::COMB_TUNING_R8 * 2 {
             freeverb.tick((0.0, 0.0));
         }
         assert_ne!(freeverb.tick((0.0, 0.0)), (0.0, 0.0));
     }
+
+    #[test]
+    fn freeze_ignores_input() {
+        let mut freeverb: super::Freeverb = super::Freeverb::new(44100);
+        freeverb.set_freeze(true);
+        for _ in 0..super::COMB_TUNING_R8 * 2 {
+            assert_eq!(freeverb.tick((1.0, 1.0)), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn reset_silences_tail() {
+        let mut freeverb: super::Freeverb = super::Freeverb::new(44100);
+        freeverb.tick((1.0, 1.0));
+        freeverb.reset();
+        for _ in 0..super::COMB_TUNING_R8 * 2 {
+            assert_eq!(freeverb.tick((0.0, 0.0)), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn clone_from_restores_tail() {
+        let mut freeverb: super::Freeverb = super::Freeverb::new(44100);
+        freeverb.tick((1.0, 1.0));
+        let snapshot = freeverb.clone();
+        let expected: Vec<_> = (0..4000).map(|_| freeverb.tick((0.0, 0.0))).collect();
+
+        freeverb.clone_from(&snapshot);
+        let restored: Vec<_> = (0..4000).map(|_| freeverb.tick((0.0, 0.0))).collect();
+        assert_eq!(restored, expected);
+    }
+
+    #[test]
+    fn double_precision_matches_single_precision() {
+        let mut single: super::Freeverb<f32> = super::Freeverb::new(44100);
+        let mut double: super::Freeverb<f64> = super::Freeverb::new(44100);
+        let mut input = (1.0, 1.0);
+        for _ in 0..10000 {
+            let (single_left, _) = single.tick((input.0 as f32, input.1 as f32));
+            let (double_left, _) = double.tick(input);
+            assert!((single_left as f64 - double_left).abs() < 1e-4);
+            input = (0.0, 0.0);
+        }
+    }
 }
@@ -4,4 +4,14 @@ mod delay_line;
 
 mod freeverb;
 
+pub use all_pass::AllPass;
 pub use freeverb::Freeverb;
+
+use num_traits::Float;
+
+/**
+ * Converts a constant to the sample type, which never fails for f32 and f64.
+ */
+fn constant<T: Float>(value: f32) -> T {
+    T::from(value).unwrap()
+}
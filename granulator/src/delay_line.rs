@@ -1,49 +1,139 @@
 use crate::frame::Frame;
+use crate::sample::Sample;
 
-pub struct DelayLine {
-    buffer: Vec<Frame>,
+// Energy below which a point counts as silent, about -120 dB.
+const SILENT_ENERGY: f32 = 1e-12;
+
+pub struct DelayLine<T = f32, const N: usize = 2> {
+    buffer: Vec<Frame<T, N>>,
     write_index: usize,
-    pub max_length: f32,
+    pub max_length: T,
+}
+
+// `clone_from` reuses the buffer, so that restoring a state doesn't allocate.
+impl<T: Sample, const N: usize> Clone for DelayLine<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            write_index: self.write_index,
+            max_length: self.max_length,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.buffer.clone_from(&source.buffer);
+        self.write_index = source.write_index;
+        self.max_length = source.max_length;
+    }
 }
 
-impl DelayLine {
+impl<T: Sample, const N: usize> DelayLine<T, N> {
     pub fn new(max_length: usize) -> Self {
         Self {
-            buffer: vec![[0.0, 0.0]; max_length],
+            buffer: vec![[T::zero(); N]; max_length],
             write_index: 0,
-            max_length: max_length as f32,
+            max_length: T::from_usize(max_length),
         }
     }
 
     /**
      * Get interpolated value from buffer.
      */
-    pub fn read(&self, delay_length: f32) -> Frame {
+    pub fn read(&self, delay_length: T) -> Frame<T, N> {
+        let (index_previous, index_next, delta) = self.interpolation_points(delay_length);
+        let previous = self.buffer[index_previous];
+
+        let mut frame = self.buffer[index_next];
+        for (sample, previous) in frame.iter_mut().zip(previous.iter()) {
+            *sample += delta * (*previous - *sample);
+        }
+        frame
+    }
+
+    /**
+     * Indices of the frames around a delay, and the weight of the previous one.
+     */
+    pub fn interpolation_points(&self, delay_length: T) -> (usize, usize, T) {
         let index_fractional = self.get_read_index_fractional(delay_length);
         let index_next = index_fractional.ceil();
         let index_next = if index_next >= self.max_length {
-            self.max_length - 1.0
+            self.max_length - T::one()
         } else {
             index_next
         };
-        let index_previous = if index_next == 0.0 {
-            self.max_length - 1.0
+        let index_previous = if index_next == T::zero() {
+            self.max_length - T::one()
         } else {
-            index_next - 1.0
+            index_next - T::one()
         };
         let delta = (index_next - index_fractional).abs();
 
-        let [previous_left, previous_right] = self.buffer[index_previous as usize];
-        let [next_left, next_right] = self.buffer[index_next as usize];
+        (index_previous.to_index(), index_next.to_index(), delta)
+    }
+
+    /**
+     * Delay within `window` samples of `delay_length` where the signal is
+     * quietest: the nearest zero crossing, or the lowest energy point when
+     * the channels never cross zero together. Each pair of neighbouring
+     * samples is searched along their linear interpolation, nearest pairs
+     * first.
+     */
+    pub fn quietest_delay(&self, delay_length: T, window: usize) -> T {
+        let silent_energy = T::from_f32(SILENT_ENERGY);
+        let start = delay_length.floor();
+        let mut quietest = (T::infinity(), delay_length);
+        for step in 0..=2 * window {
+            // 0, -1, 1, -2, 2...
+            let offset = T::from_usize(step.div_ceil(2));
+            let delay = if step % 2 == 0 {
+                start + offset
+            } else {
+                start - offset
+            };
+            let from = self.read(delay);
+            let to = self.read(delay + T::one());
+            // Point of the segment from `from` to `to` closest to silence.
+            let mut dot = T::zero();
+            let mut length = T::zero();
+            for (from, to) in from.iter().zip(to.iter()) {
+                dot += *from * (*to - *from);
+                length += (*to - *from) * (*to - *from);
+            }
+            let t = if length > T::zero() {
+                (-dot / length).max(T::zero()).min(T::one())
+            } else {
+                T::zero()
+            };
+            let energy = from
+                .iter()
+                .zip(to.iter())
+                .map(|(from, to)| {
+                    let sample = *from + t * (*to - *from);
+                    sample * sample
+                })
+                .sum::<T>();
+            if energy <= silent_energy {
+                return delay + t;
+            }
+            if energy < quietest.0 {
+                quietest = (energy, delay + t);
+            }
+        }
+        quietest.1
+    }
+
+    pub fn frame(&self, index: usize) -> Frame<T, N> {
+        self.buffer[index]
+    }
 
-        let result = [
-            next_left + delta * (previous_left - next_left),
-            next_right + delta * (previous_right - next_right),
-        ];
-        result
+    pub fn reset(&mut self) {
+        for frame in self.buffer.iter_mut() {
+            *frame = [T::zero(); N];
+        }
+        self.write_index = 0;
     }
 
-    pub fn write_and_advance(&mut self, frame: Frame) {
+    pub fn write_and_advance(&mut self, frame: Frame<T, N>) {
         self.buffer[self.write_index] = frame;
 
         if self.write_index == self.buffer.len() - 1 {
@@ -57,22 +147,42 @@ impl DelayLine {
      * Read index = write index - delay length.
      * Can be in then range [0, max_length - 1]
      */
-    fn get_read_index_fractional(&self, mut delay_length: f32) -> f32 {
-        if delay_length < 0.0 {
+    fn get_read_index_fractional(&self, mut delay_length: T) -> T {
+        if delay_length < T::zero() {
             // Trying to read the future.
-            delay_length = 0.0;
+            delay_length = T::zero();
         }
         if delay_length >= self.max_length {
             // Going to far in the past.
-            delay_length = self.max_length - 1.0;
+            delay_length = self.max_length - T::one();
         }
 
-        let write_index_f32 = self.write_index as f32;
-        let read_index = if delay_length > write_index_f32 {
-            self.max_length - 1.0 + write_index_f32 - delay_length
+        let write_index = T::from_usize(self.write_index);
+        if delay_length > write_index {
+            self.max_length - T::one() + write_index - delay_length
         } else {
-            write_index_f32 - delay_length
-        };
-        read_index
+            write_index - delay_length
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DelayLine;
+
+    #[test]
+    fn finds_nearest_zero_crossing() {
+        let mut delay_line: DelayLine = DelayLine::new(1000);
+        // Zero crossings every 100 samples of delay, from 99.5.
+        for i in 0..1000 {
+            let sample = (std::f32::consts::PI * (i as f32 + 0.5) / 100.0).sin();
+            delay_line.write_and_advance([sample, 0.5 * sample]);
+        }
+        let delay = delay_line.quietest_delay(210.0, 20);
+        assert!((delay - 199.5).abs() < 1e-3);
+        assert!(delay_line.read(delay)[0].abs() < 1e-4);
+        // No crossing within reach, the quietest point is on the window edge.
+        let delay = delay_line.quietest_delay(250.0, 20);
+        assert!((delay - 271.0).abs() < 0.5);
     }
 }
@@ -0,0 +1,160 @@
+use freeverb::AllPass;
+use std::f32::consts::PI;
+
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+// All-pass lengths at 44100 Hz. Short and mutually prime, so that transients
+// get smeared without building up an audible tail.
+const ALLPASS_TUNING_LEFT: [usize; 4] = [142, 107, 379, 277];
+const ALLPASS_TUNING_RIGHT: [usize; 4] = [149, 113, 389, 283];
+// Added to the lengths of each further pair of channels, so that
+// channels beyond stereo stay decorrelated.
+const CHANNEL_PAIR_SPREAD: usize = 17;
+const ALLPASS_FEEDBACK: f32 = 0.625;
+// Modulation depth, in samples, and rate of each stage, in Hz.
+const MODULATION_DEPTH: f32 = 6.0;
+const MODULATION_RATES: [f32; 4] = [0.31, 0.47, 0.73, 1.13];
+
+fn adjust_length(length: usize, sample_rate: f32) -> usize {
+    (length as f32 * sample_rate / 44100.0) as usize
+}
+
+fn channel_length(stage: usize, channel: usize) -> usize {
+    let tuning = [ALLPASS_TUNING_LEFT, ALLPASS_TUNING_RIGHT][channel % 2];
+    tuning[stage] + channel / 2 * CHANNEL_PAIR_SPREAD
+}
+
+struct Stage<T, const N: usize> {
+    allpasses: [AllPass<T>; N],
+    phase: f32,
+    phase_increment: f32,
+}
+
+/**
+ * Series of modulated all-pass filters, like a reverb without tail.
+ * `amount` crossfades between the input and the diffused signal.
+ */
+pub struct Diffuser<T = f32, const N: usize = 2> {
+    stages: Vec<Stage<T, N>>,
+    amount: f32,
+}
+
+// `clone_from` reuses the all-pass buffers, so that restoring a state doesn't allocate.
+impl<T: Sample, const N: usize> Clone for Diffuser<T, N> {
+    fn clone(&self) -> Self {
+        let stages = self
+            .stages
+            .iter()
+            .map(|stage| Stage {
+                allpasses: stage.allpasses.clone(),
+                ..*stage
+            })
+            .collect();
+
+        Diffuser {
+            stages,
+            amount: self.amount,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        for (stage, source_stage) in self.stages.iter_mut().zip(source.stages.iter()) {
+            stage.allpasses.clone_from(&source_stage.allpasses);
+            stage.phase = source_stage.phase;
+            stage.phase_increment = source_stage.phase_increment;
+        }
+        self.amount = source.amount;
+    }
+}
+
+impl<T: Sample, const N: usize> Diffuser<T, N> {
+    pub fn new(sample_rate: f32) -> Diffuser<T, N> {
+        let stages = (0..ALLPASS_TUNING_LEFT.len())
+            .map(|i| {
+                let allpasses = std::array::from_fn(|channel| {
+                    let mut allpass =
+                        AllPass::new(adjust_length(channel_length(i, channel), sample_rate));
+                    allpass.set_feedback(T::from_f32(ALLPASS_FEEDBACK));
+                    allpass
+                });
+                Stage {
+                    allpasses,
+                    phase: 0.0,
+                    phase_increment: 2.0 * PI * MODULATION_RATES[i] / sample_rate,
+                }
+            })
+            .collect();
+
+        Diffuser {
+            stages,
+            amount: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut() {
+            for allpass in stage.allpasses.iter_mut() {
+                allpass.reset();
+            }
+            stage.phase = 0.0;
+        }
+    }
+
+    pub fn process(&mut self, frame: Frame<T, N>) -> Frame<T, N> {
+        if self.amount == 0.0 {
+            return frame;
+        }
+        let amount = T::from_f32(self.amount);
+        let mut diffused = frame;
+
+        for stage in self.stages.iter_mut() {
+            stage.phase = (stage.phase + stage.phase_increment) % (2.0 * PI);
+            let offset = MODULATION_DEPTH * (1.0 + stage.phase.sin());
+            for (sample, allpass) in diffused.iter_mut().zip(stage.allpasses.iter_mut()) {
+                *sample = allpass.tick_modulated(*sample, T::from_f32(offset));
+            }
+        }
+
+        for (sample, input) in diffused.iter_mut().zip(frame.iter()) {
+            *sample = *input + amount * (*sample - *input);
+        }
+        diffused
+    }
+    /**
+     * 0.0 - 1.0, 0.0 bypasses the diffuser.
+     */
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn bypassed_without_amount() {
+        let mut diffuser: super::Diffuser = super::Diffuser::new(44100.0);
+        assert_eq!(diffuser.process([1.0, -1.0]), [1.0, -1.0]);
+    }
+
+    #[test]
+    fn smears_impulse_without_long_tail() {
+        let mut diffuser: super::Diffuser = super::Diffuser::new(44100.0);
+        diffuser.set_amount(1.0);
+
+        let mut impulse = [1.0, 1.0];
+        let mut early_energy = 0.0;
+        let mut late_energy = 0.0;
+        for i in 0..44100 {
+            let [left, _] = diffuser.process(impulse);
+            impulse = [0.0, 0.0];
+            if i > 0 && i < 4410 {
+                early_energy += left * left;
+            } else if i > 22050 {
+                late_energy += left * left;
+            }
+        }
+        assert!(early_energy > 0.1);
+        assert!(late_energy < 1e-6);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::filter::{DcBlocker, FilterMode, StateVariableFilter, FLAT_RESONANCE};
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+/**
+ * Tape-style soft saturation. Signals below a knee of 1.0 / (1.0 + drive)
+ * pass at unity gain, so that the saturation never raises the gain of the
+ * feedback loop, and louder ones are rounded off towards 1.0, which the
+ * output never exceeds whatever the drive.
+ */
+fn saturate<T: Sample>(input: T, drive: T) -> T {
+    let knee = T::one() / (T::one() + drive);
+    let magnitude = input.abs();
+    if magnitude <= knee {
+        return input;
+    }
+    let headroom = T::one() - knee;
+    let saturated = knee + headroom * ((magnitude - knee) / headroom).tanh();
+    saturated * input.signum()
+}
+
+/**
+ * Optional processing applied to the synthesized signal before it is fed
+ * back into the delay line: high-pass, low-pass, DC blocker and saturation.
+ */
+#[derive(Clone)]
+pub struct FeedbackProcessor<T = f32, const N: usize = 2> {
+    sample_rate: f32,
+    high_pass_cutoff: Option<f32>,
+    low_pass_cutoff: Option<f32>,
+    is_dc_blocker_enabled: bool,
+    saturation_drive: Option<f32>,
+    high_pass: [StateVariableFilter<T>; N],
+    low_pass: [StateVariableFilter<T>; N],
+    dc_blockers: [DcBlocker<T>; N],
+}
+
+impl<T: Sample, const N: usize> FeedbackProcessor<T, N> {
+    pub fn new(sample_rate: f32) -> FeedbackProcessor<T, N> {
+        let high_pass =
+            StateVariableFilter::new(FilterMode::Highpass, 20.0, FLAT_RESONANCE, sample_rate);
+        let low_pass =
+            StateVariableFilter::new(FilterMode::Lowpass, 20000.0, FLAT_RESONANCE, sample_rate);
+
+        FeedbackProcessor {
+            sample_rate,
+            high_pass_cutoff: None,
+            low_pass_cutoff: None,
+            is_dc_blocker_enabled: false,
+            saturation_drive: None,
+            high_pass: [high_pass; N],
+            low_pass: [low_pass; N],
+            dc_blockers: [DcBlocker::new(); N],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for filter in self.high_pass.iter_mut().chain(self.low_pass.iter_mut()) {
+            filter.reset();
+        }
+        for dc_blocker in self.dc_blockers.iter_mut() {
+            dc_blocker.reset();
+        }
+    }
+
+    pub fn process(&mut self, frame: Frame<T, N>) -> Frame<T, N> {
+        let mut frame = frame;
+
+        for (channel, sample) in frame.iter_mut().enumerate() {
+            if self.high_pass_cutoff.is_some() {
+                *sample = self.high_pass[channel].process(*sample);
+            }
+            if self.low_pass_cutoff.is_some() {
+                *sample = self.low_pass[channel].process(*sample);
+            }
+            if self.is_dc_blocker_enabled {
+                *sample = self.dc_blockers[channel].process(*sample);
+            }
+            if let Some(drive) = self.saturation_drive {
+                *sample = saturate(*sample, T::from_f32(drive));
+            }
+        }
+
+        frame
+    }
+
+    /**
+     * Cutoff in Hz, `None` disables the filter.
+     */
+    pub fn set_high_pass(&mut self, cutoff: Option<f32>) {
+        self.high_pass_cutoff = cutoff;
+        if let Some(cutoff) = cutoff {
+            for filter in self.high_pass.iter_mut() {
+                filter.set_cutoff_and_resonance(cutoff, FLAT_RESONANCE, self.sample_rate);
+            }
+        }
+    }
+
+    /**
+     * Cutoff in Hz, `None` disables the filter.
+     */
+    pub fn set_low_pass(&mut self, cutoff: Option<f32>) {
+        self.low_pass_cutoff = cutoff;
+        if let Some(cutoff) = cutoff {
+            for filter in self.low_pass.iter_mut() {
+                filter.set_cutoff_and_resonance(cutoff, FLAT_RESONANCE, self.sample_rate);
+            }
+        }
+    }
+
+    pub fn set_dc_blocker(&mut self, is_enabled: bool) {
+        self.is_dc_blocker_enabled = is_enabled;
+    }
+
+    /**
+     * Drive greater than 0.0, 0.0 or `None` disables the saturation.
+     */
+    pub fn set_saturation(&mut self, drive: Option<f32>) {
+        self.saturation_drive = drive.filter(|drive| *drive > 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn bypassed_by_default() {
+        let mut processor: super::FeedbackProcessor = super::FeedbackProcessor::new(44100.0);
+        assert_eq!(processor.process([0.3, -2.0]), [0.3, -2.0]);
+    }
+
+    #[test]
+    fn saturation_is_bounded() {
+        let mut processor: super::FeedbackProcessor = super::FeedbackProcessor::new(44100.0);
+        processor.set_saturation(Some(4.0));
+        let [left, right] = processor.process([100.0, -100.0]);
+        assert!((left - 1.0).abs() < 0.01);
+        assert!((right + 1.0).abs() < 0.01);
+        let [quiet, _] = processor.process([0.001, 0.0]);
+        assert_eq!(quiet, 0.001);
+
+        // More drive saturates more without losing level.
+        processor.set_saturation(Some(10.0));
+        let [full_scale, _] = processor.process([1.0, 0.0]);
+        assert!((full_scale - 0.78).abs() < 0.01);
+
+        processor.set_saturation(Some(0.0));
+        assert_eq!(processor.process([2.0, 0.0]), [2.0, 0.0]);
+    }
+
+    #[test]
+    fn saturation_is_continuous() {
+        let mut processor: super::FeedbackProcessor = super::FeedbackProcessor::new(44100.0);
+        processor.set_saturation(Some(4.0));
+        let [mut previous, _] = processor.process([-2.0, 0.0]);
+        for i in 1..=400 {
+            let input = i as f32 / 100.0 - 2.0;
+            let [output, _] = processor.process([input, 0.0]);
+            assert!(output >= previous && output - previous <= 0.01 + 1e-6);
+            previous = output;
+        }
+    }
+
+    #[test]
+    fn saturated_feedback_decays() {
+        let mut processor: super::FeedbackProcessor = super::FeedbackProcessor::new(44100.0);
+        processor.set_saturation(Some(10.0));
+        let mut sample = 1.0;
+        for _ in 0..200 {
+            let [output, _] = processor.process([sample, 0.0]);
+            sample = 0.9 * output;
+        }
+        assert!(sample.abs() < 1e-6);
+    }
+}
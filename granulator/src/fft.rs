@@ -0,0 +1,200 @@
+use crate::sample::Sample;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Complex<T = f32> {
+    pub re: T,
+    pub im: T,
+}
+
+impl<T: Sample> Complex<T> {
+    pub fn zero() -> Complex<T> {
+        Complex {
+            re: T::zero(),
+            im: T::zero(),
+        }
+    }
+
+    pub fn new(re: T, im: T) -> Complex<T> {
+        Complex { re, im }
+    }
+
+    pub fn from_polar(magnitude: T, phase: T) -> Complex<T> {
+        Complex {
+            re: magnitude * phase.cos(),
+            im: magnitude * phase.sin(),
+        }
+    }
+
+    pub fn magnitude(&self) -> T {
+        (self.re * self.re + self.im * self.im).sqrt()
+    }
+
+    pub fn phase(&self) -> T {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(&self) -> Complex<T> {
+        Complex {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+
+    fn add(self, other: Complex<T>) -> Complex<T> {
+        Complex {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+
+    fn sub(self, other: Complex<T>) -> Complex<T> {
+        Complex {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+
+    fn mul(self, other: Complex<T>) -> Complex<T> {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+/**
+ * In-place iterative radix-2 FFT. Twiddle factors and the bit reversal
+ * permutation are computed once, so transforms do not allocate.
+ */
+pub struct Fft<T = f32> {
+    size: usize,
+    twiddles: Vec<Complex<T>>,
+    bit_reversed: Vec<usize>,
+}
+
+// `clone_from` reuses the tables, so that restoring a state doesn't allocate.
+impl<T: Sample> Clone for Fft<T> {
+    fn clone(&self) -> Self {
+        Fft {
+            size: self.size,
+            twiddles: self.twiddles.clone(),
+            bit_reversed: self.bit_reversed.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.size = source.size;
+        self.twiddles.clone_from(&source.twiddles);
+        self.bit_reversed.clone_from(&source.bit_reversed);
+    }
+}
+
+impl<T: Sample> Fft<T> {
+    pub fn new(size: usize) -> Fft<T> {
+        assert!(size.is_power_of_two(), "FFT size must be a power of two");
+        let bits = size.trailing_zeros();
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let phase = -T::from_f32(2.0) * T::PI() * T::from_usize(k) / T::from_usize(size);
+                Complex::from_polar(T::one(), phase)
+            })
+            .collect();
+        let bit_reversed = (0..size)
+            .map(|i| {
+                if bits == 0 {
+                    0
+                } else {
+                    i.reverse_bits() >> (usize::BITS - bits)
+                }
+            })
+            .collect();
+
+        Fft {
+            size,
+            twiddles,
+            bit_reversed,
+        }
+    }
+
+    pub fn forward(&self, buffer: &mut [Complex<T>]) {
+        self.transform(buffer, false);
+    }
+
+    /**
+     * Inverse transform, scaled by 1 / size.
+     */
+    pub fn inverse(&self, buffer: &mut [Complex<T>]) {
+        self.transform(buffer, true);
+        let scale = T::one() / T::from_usize(self.size);
+        for value in buffer.iter_mut() {
+            value.re *= scale;
+            value.im *= scale;
+        }
+    }
+
+    fn transform(&self, buffer: &mut [Complex<T>], inverse: bool) {
+        assert_eq!(buffer.len(), self.size);
+
+        for i in 0..self.size {
+            let j = self.bit_reversed[i];
+            if j > i {
+                buffer.swap(i, j);
+            }
+        }
+
+        let mut length = 2;
+        while length <= self.size {
+            let half = length / 2;
+            let step = self.size / length;
+            for start in (0..self.size).step_by(length) {
+                for k in 0..half {
+                    let twiddle = if inverse {
+                        self.twiddles[k * step].conj()
+                    } else {
+                        self.twiddles[k * step]
+                    };
+                    let even = buffer[start + k];
+                    let odd = buffer[start + k + half].mul(twiddle);
+                    buffer[start + k] = even.add(odd);
+                    buffer[start + k + half] = even.sub(odd);
+                }
+            }
+            length *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Complex, Fft};
+    use std::f32::consts::PI;
+
+    #[test]
+    fn sine_peaks_at_its_bin() {
+        let fft: Fft = Fft::new(64);
+        let mut buffer: Vec<Complex> = (0..64)
+            .map(|i| Complex::new((2.0 * PI * 5.0 * i as f32 / 64.0).sin(), 0.0))
+            .collect();
+        fft.forward(&mut buffer);
+        for (bin, value) in buffer.iter().enumerate() {
+            if bin == 5 || bin == 59 {
+                assert!((value.magnitude() - 32.0).abs() < 1e-3);
+            } else {
+                assert!(value.magnitude() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_restores_signal() {
+        let fft: Fft = Fft::new(16);
+        let signal: Vec<Complex> = (0..16).map(|i| Complex::new(i as f32, 0.0)).collect();
+        let mut buffer = signal.clone();
+        fft.forward(&mut buffer);
+        fft.inverse(&mut buffer);
+        for (restored, original) in buffer.iter().zip(signal.iter()) {
+            assert!((restored.re - original.re).abs() < 1e-4);
+            assert!(restored.im.abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::sample::Sample;
+
+// Resonance giving a flat (Butterworth) response.
+pub const FLAT_RESONANCE: f32 = std::f32::consts::FRAC_1_SQRT_2;
+// Pole of the DC blocker, the closer to 1.0 the lower the cutoff.
+const DC_BLOCKER_POLE: f32 = 0.995;
+
+/**
+ * Coefficient of a one-pole smoother with a time constant of `seconds`,
+ * at least one sample.
+ */
+pub(crate) fn one_pole_coefficient<T: Sample>(seconds: f32, sample_rate: f32) -> T {
+    let samples = (seconds * sample_rate).max(1.0);
+    T::from_f32(1.0 - (-1.0 / samples).exp())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterMode {
+    Lowpass,
+    // Constant peak gain, the bandwidth narrows as the resonance rises.
+    Bandpass,
+    Highpass,
+}
+
+/**
+ * Topology-preserving transform state-variable filter.
+ * Stays stable while cutoff and resonance are modulated.
+ */
+#[derive(Copy, Clone)]
+pub struct StateVariableFilter<T = f32> {
+    mode: FilterMode,
+    a1: T,
+    a2: T,
+    a3: T,
+    // 1 / resonance.
+    damping: T,
+    state1: T,
+    state2: T,
+}
+
+impl<T: Sample> StateVariableFilter<T> {
+    pub fn new(mode: FilterMode, cutoff: f32, resonance: f32, sample_rate: f32) -> Self {
+        let mut filter = StateVariableFilter {
+            mode,
+            a1: T::zero(),
+            a2: T::zero(),
+            a3: T::zero(),
+            damping: T::zero(),
+            state1: T::zero(),
+            state2: T::zero(),
+        };
+        filter.set_cutoff_and_resonance(cutoff, resonance, sample_rate);
+        filter
+    }
+
+    /**
+     * Cutoff is in Hz, resonance is the quality factor of the filter.
+     */
+    pub fn set_cutoff_and_resonance(&mut self, cutoff: f32, resonance: f32, sample_rate: f32) {
+        let cutoff = T::from_f32(cutoff.clamp(1.0, sample_rate * 0.49));
+        let g = (T::PI() * cutoff / T::from_f32(sample_rate)).tan();
+        self.damping = T::one() / T::from_f32(resonance.max(0.01));
+        self.a1 = T::one() / (T::one() + g * (g + self.damping));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    pub fn reset(&mut self) {
+        self.state1 = T::zero();
+        self.state2 = T::zero();
+    }
+
+    pub fn process(&mut self, input: T) -> T {
+        let v3 = input - self.state2;
+        let v1 = self.a1 * self.state1 + self.a2 * v3;
+        let v2 = self.state2 + self.a2 * self.state1 + self.a3 * v3;
+        let two = T::from_f32(2.0);
+        self.state1 = two * v1 - self.state1;
+        self.state2 = two * v2 - self.state2;
+
+        match self.mode {
+            FilterMode::Lowpass => v2,
+            FilterMode::Bandpass => self.damping * v1,
+            FilterMode::Highpass => input - self.damping * v1 - v2,
+        }
+    }
+}
+
+/**
+ * One pole, one zero high-pass removing the DC offset.
+ */
+#[derive(Copy, Clone)]
+pub struct DcBlocker<T = f32> {
+    previous_input: T,
+    previous_output: T,
+}
+
+impl<T: Sample> DcBlocker<T> {
+    pub fn new() -> Self {
+        DcBlocker {
+            previous_input: T::zero(),
+            previous_output: T::zero(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = DcBlocker::new();
+    }
+
+    pub fn process(&mut self, input: T) -> T {
+        let output =
+            input - self.previous_input + T::from_f32(DC_BLOCKER_POLE) * self.previous_output;
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DcBlocker, FilterMode, StateVariableFilter, FLAT_RESONANCE};
+
+    fn settle(filter: &mut StateVariableFilter, signal: impl Fn(usize) -> f32) -> f32 {
+        let mut peak: f32 = 0.0;
+        for i in 0..4000 {
+            let output = filter.process(signal(i));
+            if i > 2000 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    fn dc(_: usize) -> f32 {
+        1.0
+    }
+
+    fn nyquist(i: usize) -> f32 {
+        1.0 - 2.0 * (i % 2) as f32
+    }
+
+    fn filter(mode: FilterMode) -> StateVariableFilter {
+        StateVariableFilter::new(mode, 1000.0, FLAT_RESONANCE, 44100.0)
+    }
+
+    #[test]
+    fn lowpass_passes_dc_and_removes_nyquist() {
+        assert!((settle(&mut filter(FilterMode::Lowpass), dc) - 1.0).abs() < 1e-3);
+        assert!(settle(&mut filter(FilterMode::Lowpass), nyquist) < 1e-3);
+    }
+
+    #[test]
+    fn highpass_removes_dc_and_passes_nyquist() {
+        assert!(settle(&mut filter(FilterMode::Highpass), dc) < 1e-3);
+        assert!((settle(&mut filter(FilterMode::Highpass), nyquist) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn bandpass_passes_cutoff_only() {
+        let cutoff = |i: usize| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0).sin();
+        assert!((settle(&mut filter(FilterMode::Bandpass), cutoff) - 1.0).abs() < 1e-2);
+        assert!(settle(&mut filter(FilterMode::Bandpass), dc) < 1e-3);
+        assert!(settle(&mut filter(FilterMode::Bandpass), nyquist) < 1e-3);
+    }
+
+    #[test]
+    fn dc_blocker_removes_offset() {
+        let mut dc_blocker: DcBlocker = DcBlocker::new();
+        let mut output = 1.0;
+        for _ in 0..10000 {
+            output = dc_blocker.process(1.0);
+        }
+        assert!(output.abs() < 1e-3);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::filter::one_pole_coefficient;
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+// Default attack and release times, in seconds.
+const ATTACK: f32 = 0.01;
+const RELEASE: f32 = 0.2;
+
+/**
+ * Peak envelope of the input with separate attack and release times.
+ * The value is amplified by `gain` and limited to 0.0 - 1.0.
+ */
+#[derive(Clone)]
+pub struct EnvelopeFollower<T = f32> {
+    sample_rate: f32,
+    attack: T,
+    release: T,
+    gain: T,
+    envelope: T,
+}
+
+impl<T: Sample> EnvelopeFollower<T> {
+    pub fn new(sample_rate: f32) -> EnvelopeFollower<T> {
+        EnvelopeFollower {
+            sample_rate,
+            attack: one_pole_coefficient(ATTACK, sample_rate),
+            release: one_pole_coefficient(RELEASE, sample_rate),
+            gain: T::one(),
+            envelope: T::zero(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = T::zero();
+    }
+
+    pub fn process<const N: usize>(&mut self, frame: Frame<T, N>) -> f32 {
+        let level = frame
+            .iter()
+            .fold(T::zero(), |level, sample| level.max(sample.abs()));
+        let coefficient = if level > self.envelope {
+            self.attack
+        } else {
+            self.release
+        };
+        self.envelope += coefficient * (level - self.envelope);
+        self.value()
+    }
+
+    /**
+     * Current envelope after gain, 0.0 - 1.0.
+     */
+    pub fn value(&self) -> f32 {
+        (self.envelope * self.gain).min(T::one()).to_parameter()
+    }
+
+    pub fn set_attack(&mut self, milliseconds: f32) {
+        self.attack = one_pole_coefficient(milliseconds * 0.001, self.sample_rate);
+    }
+
+    pub fn set_release(&mut self, milliseconds: f32) {
+        self.release = one_pole_coefficient(milliseconds * 0.001, self.sample_rate);
+    }
+
+    pub fn set_gain(&mut self, decibels: f32) {
+        self.gain = T::from_f32(10.0f32.powf(decibels / 20.0));
+    }
+}
+
+/**
+ * Scale applied to a modulated parameter. With a positive `depth` silence
+ * scales it down by `depth` and a full envelope leaves it unchanged, with a
+ * negative `depth` it is the reverse.
+ */
+pub fn modulation(depth: f32, envelope: f32) -> f32 {
+    if depth >= 0.0 {
+        1.0 - depth * (1.0 - envelope)
+    } else {
+        1.0 + depth * envelope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{modulation, EnvelopeFollower};
+
+    #[test]
+    fn follows_attack_and_release() {
+        let mut follower: EnvelopeFollower = EnvelopeFollower::new(1000.0);
+        follower.set_attack(10.0);
+        follower.set_release(100.0);
+        for _ in 0..10 {
+            follower.process([0.5, -0.5]);
+        }
+        // One time constant.
+        assert!((follower.value() - 0.5 * 0.632).abs() < 0.02);
+        for _ in 0..100 {
+            follower.process([0.5, 0.5]);
+        }
+        assert!((follower.value() - 0.5).abs() < 1e-3);
+        for _ in 0..100 {
+            follower.process([0.0, 0.0]);
+        }
+        assert!((follower.value() - 0.5 * 0.368).abs() < 0.02);
+
+        follower.set_gain(40.0);
+        assert_eq!(follower.value(), 1.0);
+    }
+
+    #[test]
+    fn depth_sets_direction() {
+        assert_eq!(modulation(0.0, 0.3), 1.0);
+        assert_eq!(modulation(1.0, 0.0), 0.0);
+        assert_eq!(modulation(1.0, 1.0), 1.0);
+        assert_eq!(modulation(-1.0, 0.0), 1.0);
+        assert_eq!(modulation(-1.0, 1.0), 0.0);
+        assert_eq!(modulation(0.5, 0.0), 0.5);
+    }
+}
@@ -1,3 +1,4 @@
-pub type Frame = [f32; 2];
-
-pub const SILENT_FRAME: Frame = [0.0, 0.0];
+/**
+ * One sample for each of the `N` channels.
+ */
+pub type Frame<T = f32, const N: usize = 2> = [T; N];
@@ -1,62 +1,142 @@
-use crate::frame::{Frame, SILENT_FRAME};
+use crate::frame::Frame;
+use crate::sample::Sample;
 
+#[cfg(test)]
 use crate::delay_line::DelayLine;
+#[cfg(test)]
 use crate::parabolic_envelope::ParabolicEnvelope;
 
-const GRAIN_AMPLITUDE: f32 = 0.7;
+pub const GRAIN_AMPLITUDE: f32 = 0.7;
 
+/**
+ * Equal power gains for a position on the ring of output channels, 0.0 - N.
+ * Whole positions are on a single channel, the grain fades between
+ * neighbouring channels in between, and from the last back to the first one.
+ */
+pub fn placement_gains<T: Sample, const N: usize>(placement: T) -> Frame<T, N> {
+    let mut gains = [T::zero(); N];
+    if N == 1 {
+        gains[0] = T::one();
+        return gains;
+    }
+    let num_channels = T::from_usize(N);
+    let placement = placement % num_channels;
+    let placement = if placement < T::zero() {
+        placement + num_channels
+    } else {
+        placement
+    };
+    let channel = placement.to_index().min(N - 1);
+    let fraction = (placement - T::from_usize(channel)) * T::FRAC_PI_2();
+    gains[channel] = fraction.cos();
+    gains[(channel + 1) % N] = fraction.sin();
+    gains
+}
+
+/**
+ * Scalar synthesis of a single grain, the reference for `GrainPool`.
+ */
+#[cfg(test)]
 #[derive(Copy, Clone)]
-pub struct Grain {
+pub struct Grain<T = f32, const N: usize = 2> {
     pub is_active: bool,
-    duration_samples: f32,
-    envelope: ParabolicEnvelope,
+    duration_samples: T,
+    envelope: ParabolicEnvelope<T>,
     // Position to read from delay line.
-    position: f32,
-    num_samples_played: f32,
+    position: T,
+    num_samples_played: T,
 
-    pitch: f32,
+    pitch: T,
+    // How much of the grain goes to its own place rather than
+    // to the channels it was read from, 0.0 - 1.0
+    spread: T,
+    // Gain of the grain in each output channel, see `placement_gains`.
+    placement: Frame<T, N>,
 }
 
-impl Grain {
-    pub fn new(position: f32, duration_samples: f32, pitch: f32) -> Grain {
+#[cfg(test)]
+impl<T: Sample, const N: usize> Grain<T, N> {
+    pub fn new(position: T, duration_samples: T, pitch: T) -> Grain<T, N> {
         Grain {
             is_active: false,
             duration_samples,
-            envelope: ParabolicEnvelope::new(duration_samples as f32, GRAIN_AMPLITUDE),
+            envelope: ParabolicEnvelope::new(duration_samples, T::from_f32(GRAIN_AMPLITUDE)),
             position,
-            num_samples_played: 0.0,
+            num_samples_played: T::zero(),
             pitch,
+            spread: T::zero(),
+            placement: [T::zero(); N],
         }
     }
 
-    pub fn process(&mut self, delay_line: &DelayLine) -> Frame {
-        if self.is_active == false {
-            return SILENT_FRAME;
+    pub fn process(&mut self, delay_line: &DelayLine<T, N>) -> Frame<T, N> {
+        if !self.is_active {
+            return [T::zero(); N];
         }
         let env = self.envelope.process();
 
-        let [left, right] = delay_line.read(self.position);
+        let mut frame = delay_line.read(self.position);
+        if self.spread > T::zero() {
+            let mono = frame.iter().copied().sum::<T>() / T::from_usize(N);
+            for (sample, gain) in frame.iter_mut().zip(self.placement.iter()) {
+                *sample += self.spread * (mono * *gain - *sample);
+            }
+        }
 
         self.num_samples_played += self.pitch;
-        self.position += 1.0 - self.pitch;
+        self.position += T::one() - self.pitch;
 
         if self.num_samples_played >= self.duration_samples {
             self.is_active = false;
-            self.num_samples_played = 0.0;
+            self.num_samples_played = T::zero();
         }
 
-        [left * env, right * env]
+        for sample in frame.iter_mut() {
+            *sample *= env;
+        }
+        frame
     }
 
-    pub fn activate(&mut self, position: f32, duration_samples: f32, pitch: f32) {
-        if self.is_active == true {
+    /**
+     * `placement` is the position of the grain across the output channels,
+     * see `placement_gains`.
+     */
+    pub fn activate(
+        &mut self,
+        position: T,
+        duration_samples: T,
+        pitch: T,
+        spread: T,
+        placement: T,
+    ) {
+        if self.is_active {
             return;
         }
         self.position = position;
         self.duration_samples = duration_samples;
-        self.num_samples_played = 0.0;
+        self.num_samples_played = T::zero();
         self.pitch = pitch;
-        self.envelope = ParabolicEnvelope::new(duration_samples / self.pitch, GRAIN_AMPLITUDE);
+        self.spread = spread;
+        self.placement = placement_gains(placement);
+        self.envelope =
+            ParabolicEnvelope::new(duration_samples / self.pitch, T::from_f32(GRAIN_AMPLITUDE));
         self.is_active = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::placement_gains;
+
+    #[test]
+    fn placement_keeps_power() {
+        for i in 0..32 {
+            let placement = i as f32 / 8.0;
+            let gains: [f32; 4] = placement_gains(placement);
+            let power: f32 = gains.iter().map(|gain| gain * gain).sum();
+            assert!((power - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(placement_gains::<f32, 4>(2.0), [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(placement_gains::<f64, 1>(0.7), [1.0]);
+    }
+}
@@ -0,0 +1,63 @@
+/**
+ * Amplitude envelope of a grain.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GrainEnvelope {
+    // Smooth rise and fall, the default.
+    Parabolic,
+    // Linear attack and release as fractions of the grain length,
+    // full amplitude in between.
+    Trapezoid { attack: f32, release: f32 },
+}
+
+/**
+ * Everything that sets a grain apart when it starts.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GrainParameters {
+    // Delay of the read head, in samples, finite. Clamped to the length of
+    // the delay line.
+    pub position: f32,
+    // Length of the grain in samples of the delay line, finite, so that it
+    // plays for `duration / pitch` samples.
+    pub duration: f32,
+    // Playback rate, 1.0 is the original pitch. Finite and above 0.0,
+    // otherwise the grain would never end.
+    pub pitch: f32,
+    // Position across the output channels, 0.0 - N, whole values are on a
+    // single channel. `grain_spread` sets how much the grain moves there.
+    pub pan: f32,
+    // Gain on top of the envelope, 1.0 by default.
+    pub amplitude: f32,
+    pub envelope: GrainEnvelope,
+}
+
+impl GrainParameters {
+    /**
+     * Whether a grain with these parameters ends, see the fields.
+     */
+    pub fn is_playable(&self) -> bool {
+        self.position.is_finite()
+            && self.duration.is_finite()
+            && self.pitch.is_finite()
+            && self.pitch > 0.0
+    }
+}
+
+/**
+ * Decides the parameters of each new grain, see
+ * `Granulator::set_grain_parameter_source`.
+ *
+ * `Granulator` calls it when the scheduler or an onset starts a grain, with
+ * the parameters the grain would have had: the current position (modulated),
+ * duration and pitch, a random pan, unit amplitude and the parabolic
+ * envelope. Returning them unchanged keeps the usual behaviour, so a source
+ * can replace only what it needs, from its own distributions, Markov chains
+ * or external data. Parameters that are not playable, see
+ * `GrainParameters::is_playable`, are replaced by the defaults.
+ *
+ * It is called on the audio thread and should not allocate or block.
+ */
+pub trait GrainParameterSource: Send {
+    fn next_grain(&mut self, defaults: GrainParameters) -> GrainParameters;
+}
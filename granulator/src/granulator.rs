@@ -1,12 +1,32 @@
 use crate::delay_line::DelayLine;
-use crate::frame::{Frame, SILENT_FRAME};
-use crate::grain::Grain;
+use crate::diffuser::Diffuser;
+use crate::feedback::FeedbackProcessor;
+use crate::filter::{FilterMode, FLAT_RESONANCE};
+use crate::follower::{self, EnvelopeFollower};
+use crate::frame::Frame;
+use crate::grain_pool::{GrainPool, VoiceFilter};
+use crate::grain_source::{GrainEnvelope, GrainParameterSource, GrainParameters};
+use crate::looper::Looper;
+use crate::mixer::{CrossfadeLaw, Mixer};
+use crate::morph::Morph;
+use crate::onset::{Burst, OnsetDetector};
+use crate::parameter::{ParameterId, NUM_PARAMETERS};
+use crate::pattern::{Pattern, PatternStep, Sequencer};
+use crate::reverb::Reverb;
+use crate::sample::Sample;
 use crate::scheduler::Scheduler;
-use freeverb::Freeverb;
+use crate::spectral::{self, Spectral};
+use crate::units;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 const DEFAULT_SAMPLE_RATE: usize = 41000;
 const MAX_DELAY_TIME_SECONDS: usize = 10;
-const MAX_GRAINS: usize = 100;
+const DEFAULT_MAX_GRAINS: usize = 100;
+// Upper limit of `set_max_grains`.
+pub const MAX_POLYPHONY: usize = 4096;
+// Grains waiting to be started, see `Granulator::trigger_grain`.
+pub const MAX_PENDING_GRAINS: usize = 256;
 
 type Density = f32;
 type Position = f32;
@@ -15,177 +35,1180 @@ type Pitch = f32;
 type Volume = f32;
 type Feedback = f32;
 type WetDry = f32;
+type Level = f32;
+type Frequency = f32;
+type Drive = f32;
+type Diffusion = f32;
+type ReverbAmount = f32;
 
 type NewGrainHook = fn(duration: Duration);
 
-pub struct Granulator {
+/**
+ * Playback engine used to read from the delay line.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mode {
+    // Cloud of grains activated by the scheduler.
+    Cloud,
+    // Loop of `duration` samples at `position`, replayed continuously.
+    Loop,
+    // Overlapping FFT frames at `position`, processed bin by bin.
+    Spectral,
+}
+
+impl Mode {
+    pub(crate) fn from_index(index: usize) -> Mode {
+        match index {
+            0 => Mode::Cloud,
+            1 => Mode::Loop,
+            _ => Mode::Spectral,
+        }
+    }
+}
+
+/**
+ * What starts new grains in cloud mode.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GrainTrigger {
+    // Only the scheduler, following density or overlap.
+    Scheduler,
+    // The scheduler, and transients detected in the input.
+    SchedulerAndOnsets,
+    // Only transients detected in the input.
+    Onsets,
+    // Steps of the pattern, on the tempo clock, see `Granulator::set_pattern`.
+    Pattern,
+}
+
+impl GrainTrigger {
+    pub(crate) fn from_index(index: usize) -> GrainTrigger {
+        match index {
+            0 => GrainTrigger::Scheduler,
+            1 => GrainTrigger::SchedulerAndOnsets,
+            2 => GrainTrigger::Onsets,
+            _ => GrainTrigger::Pattern,
+        }
+    }
+}
+
+/**
+ * State-variable filter applied to each grain in cloud mode.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GrainFilter {
+    Off,
+    Lowpass,
+    Bandpass,
+    Highpass,
+}
+
+impl GrainFilter {
+    pub(crate) fn from_index(index: usize) -> GrainFilter {
+        match index {
+            0 => GrainFilter::Off,
+            1 => GrainFilter::Lowpass,
+            2 => GrainFilter::Bandpass,
+            _ => GrainFilter::Highpass,
+        }
+    }
+
+    fn filter_mode(self) -> Option<FilterMode> {
+        match self {
+            GrainFilter::Off => None,
+            GrainFilter::Lowpass => Some(FilterMode::Lowpass),
+            GrainFilter::Bandpass => Some(FilterMode::Bandpass),
+            GrainFilter::Highpass => Some(FilterMode::Highpass),
+        }
+    }
+}
+
+/**
+ * Where the reverb is applied in the signal path.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReverbRouting {
+    // Reverb is not processed at all.
+    Bypass,
+    // Only the synthesized grains, before mixing with the dry signal.
+    Grains,
+    // The whole output, dry signal included.
+    Output,
+    // The signal fed back into the delay line.
+    Feedback,
+}
+
+impl ReverbRouting {
+    pub(crate) fn from_index(index: usize) -> ReverbRouting {
+        match index {
+            0 => ReverbRouting::Bypass,
+            1 => ReverbRouting::Grains,
+            2 => ReverbRouting::Output,
+            _ => ReverbRouting::Feedback,
+        }
+    }
+}
+
+/**
+ * Granular processor with `N` input and output channels, the delay line
+ * and all processing run on every channel. Audio and processing state are
+ * of type `T`, `f32` or `f64`, parameters are always `f32`.
+ */
+pub struct Granulator<T = f32, const N: usize = 2> {
+    mode: Mode,
     scheduler: Scheduler,
-    grains_pool: [Grain; MAX_GRAINS],
-    delay_line: DelayLine,
+    grain_trigger: GrainTrigger,
+    onset_detector: OnsetDetector<T>,
+    onset_burst: usize,
+    burst: Burst,
+    sequencer: Sequencer,
+    looper: Looper<T>,
+    spectral: Spectral<T, N>,
+    grains_pool: GrainPool<T, N>,
+    delay_line: DelayLine<T, N>,
     position: Position,
     duration: Duration,
     pitch: Pitch,
     volume: Volume,
     feedback: Feedback,
-    wet_dry: WetDry,
+    feedback_processor: FeedbackProcessor<T, N>,
+    mixer: Mixer<T, N>,
+    dry_latency_compensation: bool,
     pub new_grain_hook: Option<NewGrainHook>,
-    freeverb: Freeverb,
+    grain_parameter_source: Option<Box<dyn GrainParameterSource>>,
+    // Triggered grains and the number of samples until they start.
+    pending_grains: Vec<(usize, GrainParameters)>,
+    diffuser: Diffuser<T, N>,
+    grain_spread: f32,
+    // Search window for quiet grain starts, in samples, 0 when off.
+    zero_crossing_window: usize,
+    grain_filter: GrainFilter,
+    grain_filter_cutoff: f32,
+    grain_filter_cutoff_spread: f32,
+    grain_filter_resonance: f32,
+    grain_filter_resonance_spread: f32,
+    grain_filter_sweep: f32,
+    reverb_routing: ReverbRouting,
+    reverb: Reverb<T, N>,
+    morph: Option<Morph>,
+    envelope_follower: EnvelopeFollower<T>,
+    // Last value of the envelope follower, 0.0 - 1.0.
+    envelope: f32,
+    envelope_to_density: f32,
+    envelope_to_volume: f32,
+    envelope_to_feedback: f32,
+    envelope_to_position: f32,
+    // Source of all randomness, so that the state can be restored exactly.
+    rng: StdRng,
+    // Plain values of all parameters, as last set.
+    parameters: [f32; NUM_PARAMETERS],
+    sample_rate: usize,
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct GranulatorOptions {
-    // 1 - 410000
+    pub mode: Mode,
+    // In samples at the engine's sample rate, see `for_sample_rate`. The
+    // `ParameterId::Position` range is 0.1 - 10000 ms, and
+    // `Granulator::set_position_milliseconds` takes milliseconds.
     pub position: Position,
     // 1.0 - 100.0
     pub density: Density,
-    // in samples, commonly 400 - 3000 samples (so that it matches 10 to 70 ms for 41000 sr)
+    // Cloud mode only: average number of overlapping grains, 0.5 - 16.0.
+    // Replaces `density`, the grain rate then follows duration and pitch.
+    pub overlap: Option<f32>,
+    // Cloud mode only: grains started by the scheduler, input transients,
+    // both, or a rhythmic pattern.
+    pub grain_trigger: GrainTrigger,
+    // 0.0 - 1.0, higher values detect softer transients.
+    pub onset_sensitivity: f32,
+    // Time after a transient during which no other is detected, in milliseconds.
+    pub onset_refractory: f32,
+    // Number of grains started by each transient, spread over one grain length.
+    pub onset_burst: usize,
+    // Pattern clock in beats per minute, and steps per beat.
+    pub tempo: f32,
+    pub pattern_division: usize,
+    // In samples at the engine's sample rate, see `for_sample_rate`. The
+    // `ParameterId::Duration` range is 0.2 - 10000 ms, and
+    // `Granulator::set_duration_milliseconds` takes milliseconds.
     pub duration: Duration,
     // 0.1 - 10.0
     pub pitch: Pitch,
+    // Cloud mode only: 0.0 - grains keep the channels they were read from,
+    // 1.0 - each grain is placed at a random position across the channels.
+    pub grain_spread: f32,
+    // Cloud mode only: moves the start of each grain to the nearest zero
+    // crossing, or quietest point, within this many milliseconds, so that
+    // grains with sharp envelopes start without a click. 0.0 - off.
+    pub zero_crossing_window: f32,
+    // Cloud mode only: filter applied to each grain, with a cutoff (Hz) and
+    // resonance drawn for each grain around the base values.
+    pub grain_filter: GrainFilter,
+    pub grain_filter_cutoff: f32,
+    // Random cutoff offset of each grain, up to this many octaves, 0.0 - 4.0
+    pub grain_filter_cutoff_spread: f32,
+    // Quality factor, 0.5 - 20.0
+    pub grain_filter_resonance: f32,
+    // 0.0 - 1.0, up to 4 times higher or lower resonance for each grain.
+    pub grain_filter_resonance_spread: f32,
+    // Cutoff change over the lifetime of each grain, in octaves, -8.0 - 8.0
+    pub grain_filter_sweep: f32,
     pub volume: Volume,
     pub feedback: Feedback,
+    // Processing of the feedback signal, `None` or `false` bypasses a stage.
+    // Cutoff in Hz.
+    pub feedback_high_pass: Option<Frequency>,
+    // Cutoff in Hz.
+    pub feedback_low_pass: Option<Frequency>,
+    pub feedback_dc_blocker: bool,
+    // Saturation drive, 0.0 - 10.0, 0.0 or `None` disables it.
+    pub feedback_saturation: Option<Drive>,
+    // 0.0 - only dry, 1.0 - only wet
     pub wet_dry: WetDry,
+    pub crossfade_law: CrossfadeLaw,
+    // Level of the dry signal, applied on top of the crossfade.
+    pub dry_level: Level,
+    // Level of the wet signal, applied on top of the crossfade.
+    pub wet_level: Level,
+    // Delay the dry signal by the processing latency of the current mode.
+    pub dry_latency_compensation: bool,
+    // Smearing of grain transients, 0.0 - 1.0
+    pub diffusion: Diffusion,
+    pub reverb_routing: ReverbRouting,
+    // Reverb wet/dry, 0.0 - 1.0
+    pub reverb_amount: ReverbAmount,
+    // 0.0 - 1.0
+    pub reverb_room_size: f32,
+    // 0.0 - 1.0
+    pub reverb_damping: f32,
+    // Stereo width, 0.0 - 1.0
+    pub reverb_width: f32,
+    // Hold the reverb tail indefinitely.
+    pub reverb_freeze: bool,
+    // Spectral mode only: hold the current spectrum.
+    pub spectral_freeze: bool,
+    // Spectral mode only: 0.0 - 1.0
+    pub spectral_phase_randomization: f32,
+    // Spectral mode only: 0.0 - 1.0
+    pub spectral_quantization: f32,
+    // Envelope follower of the input, in milliseconds.
+    pub envelope_attack: f32,
+    pub envelope_release: f32,
+    // Envelope follower gain in dB, -12.0 - 36.0.
+    pub envelope_gain: f32,
+    // Modulation depths of the envelope, -1.0 - 1.0. Positive depths turn the
+    // parameter down in silence, negative depths turn it down when loud.
+    pub envelope_to_density: f32,
+    pub envelope_to_volume: f32,
+    pub envelope_to_feedback: f32,
+    pub envelope_to_position: f32,
+    // Position between the snapshots of `Granulator::set_morph`, 0.0 - 1.0
+    pub morph_x: f32,
+    pub morph_y: f32,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub new_grain_hook: Option<NewGrainHook>,
 }
 
 impl Default for GranulatorOptions {
     fn default() -> Self {
         GranulatorOptions {
+            mode: Mode::Cloud,
             position: DEFAULT_SAMPLE_RATE as f32,
             density: 50.0,
+            overlap: None,
+            grain_trigger: GrainTrigger::Scheduler,
+            onset_sensitivity: 0.5,
+            onset_refractory: 100.0,
+            onset_burst: 1,
+            tempo: 120.0,
+            pattern_division: 4,
             duration: 3000.0,
             pitch: 1.0,
+            grain_spread: 0.0,
+            zero_crossing_window: 0.0,
+            grain_filter: GrainFilter::Off,
+            grain_filter_cutoff: 1000.0,
+            grain_filter_cutoff_spread: 0.0,
+            grain_filter_resonance: FLAT_RESONANCE,
+            grain_filter_resonance_spread: 0.0,
+            grain_filter_sweep: 0.0,
             volume: 0.5,
             feedback: 0.6,
+            feedback_high_pass: None,
+            feedback_low_pass: None,
+            feedback_dc_blocker: false,
+            feedback_saturation: None,
             wet_dry: 1.0,
+            crossfade_law: CrossfadeLaw::Linear,
+            dry_level: 1.0,
+            wet_level: 1.0,
+            dry_latency_compensation: false,
+            diffusion: 0.0,
+            reverb_routing: ReverbRouting::Output,
+            reverb_amount: 1.0,
+            reverb_room_size: 0.1,
+            reverb_damping: 0.1,
+            reverb_width: 0.1,
+            reverb_freeze: false,
+            spectral_freeze: false,
+            spectral_phase_randomization: 0.0,
+            spectral_quantization: 0.0,
+            envelope_attack: 10.0,
+            envelope_release: 200.0,
+            envelope_gain: 0.0,
+            envelope_to_density: 0.0,
+            envelope_to_volume: 0.0,
+            envelope_to_feedback: 0.0,
+            envelope_to_position: 0.0,
+            morph_x: 0.0,
+            morph_y: 0.0,
             new_grain_hook: None,
         }
     }
 }
 
-impl Granulator {
-    pub fn new(options: GranulatorOptions) -> Granulator {
-        let position = options.position;
-        let duration = options.duration;
-        let density = options.density;
-        let pitch = options.pitch;
-        let volume = options.volume;
-        let feedback = options.feedback;
-        let wet_dry = options.wet_dry;
-        let new_grain_hook = options.new_grain_hook;
-        let delay_line = DelayLine::new(MAX_DELAY_TIME_SECONDS * DEFAULT_SAMPLE_RATE);
+impl GranulatorOptions {
+    /**
+     * Default options with positions and durations in samples at
+     * `sample_rate`, so that they keep their length in time.
+     */
+    pub fn for_sample_rate(sample_rate: usize) -> GranulatorOptions {
+        let defaults = GranulatorOptions::default();
+        let ratio = sample_rate as f32 / DEFAULT_SAMPLE_RATE as f32;
+        GranulatorOptions {
+            position: defaults.position * ratio,
+            duration: defaults.duration * ratio,
+            ..defaults
+        }
+    }
+
+    /**
+     * Plain values of all parameters, indexed by `ParameterId`, with
+     * positions and durations taken to be in samples at `sample_rate`.
+     */
+    pub(crate) fn parameter_values(&self, sample_rate: usize) -> [f32; NUM_PARAMETERS] {
+        let optional = |id: ParameterId, plain: Option<f32>| {
+            let descriptor = id.descriptor();
+            plain.unwrap_or_else(|| descriptor.off.unwrap_or(descriptor.default))
+        };
+        let mut values = [0.0; NUM_PARAMETERS];
+        for id in ParameterId::ALL.iter() {
+            values[id.index()] = match id {
+                ParameterId::Mode => self.mode as usize as f32,
+                ParameterId::Position => units::samples_to_milliseconds(self.position, sample_rate),
+                ParameterId::Density => self.density,
+                ParameterId::Overlap => optional(*id, self.overlap),
+                ParameterId::GrainTrigger => self.grain_trigger as usize as f32,
+                ParameterId::OnsetSensitivity => self.onset_sensitivity,
+                ParameterId::OnsetRefractory => self.onset_refractory,
+                ParameterId::OnsetBurst => self.onset_burst as f32,
+                ParameterId::Tempo => self.tempo,
+                ParameterId::PatternDivision => self.pattern_division as f32,
+                ParameterId::Duration => units::samples_to_milliseconds(self.duration, sample_rate),
+                ParameterId::Pitch => self.pitch,
+                ParameterId::GrainSpread => self.grain_spread,
+                ParameterId::ZeroCrossingWindow => self.zero_crossing_window,
+                ParameterId::GrainFilter => self.grain_filter as usize as f32,
+                ParameterId::GrainFilterCutoff => self.grain_filter_cutoff,
+                ParameterId::GrainFilterCutoffSpread => self.grain_filter_cutoff_spread,
+                ParameterId::GrainFilterResonance => self.grain_filter_resonance,
+                ParameterId::GrainFilterResonanceSpread => self.grain_filter_resonance_spread,
+                ParameterId::GrainFilterSweep => self.grain_filter_sweep,
+                ParameterId::Volume => self.volume,
+                ParameterId::Feedback => self.feedback,
+                ParameterId::FeedbackHighPass => optional(*id, self.feedback_high_pass),
+                ParameterId::FeedbackLowPass => optional(*id, self.feedback_low_pass),
+                ParameterId::FeedbackDcBlocker => self.feedback_dc_blocker as u8 as f32,
+                ParameterId::FeedbackSaturation => optional(*id, self.feedback_saturation),
+                ParameterId::WetDry => self.wet_dry,
+                ParameterId::CrossfadeLaw => self.crossfade_law as usize as f32,
+                ParameterId::DryLevel => self.dry_level,
+                ParameterId::WetLevel => self.wet_level,
+                ParameterId::DryLatencyCompensation => self.dry_latency_compensation as u8 as f32,
+                ParameterId::Diffusion => self.diffusion,
+                ParameterId::ReverbRouting => self.reverb_routing as usize as f32,
+                ParameterId::ReverbAmount => self.reverb_amount,
+                ParameterId::ReverbRoomSize => self.reverb_room_size,
+                ParameterId::ReverbDamping => self.reverb_damping,
+                ParameterId::ReverbWidth => self.reverb_width,
+                ParameterId::ReverbFreeze => self.reverb_freeze as u8 as f32,
+                ParameterId::SpectralFreeze => self.spectral_freeze as u8 as f32,
+                ParameterId::SpectralPhaseRandomization => self.spectral_phase_randomization,
+                ParameterId::SpectralQuantization => self.spectral_quantization,
+                ParameterId::EnvelopeAttack => self.envelope_attack,
+                ParameterId::EnvelopeRelease => self.envelope_release,
+                ParameterId::EnvelopeGain => self.envelope_gain,
+                ParameterId::EnvelopeToDensity => self.envelope_to_density,
+                ParameterId::EnvelopeToVolume => self.envelope_to_volume,
+                ParameterId::EnvelopeToFeedback => self.envelope_to_feedback,
+                ParameterId::EnvelopeToPosition => self.envelope_to_position,
+                ParameterId::MorphX => self.morph_x,
+                ParameterId::MorphY => self.morph_y,
+            };
+        }
+        values
+    }
+}
+
+/**
+ * Complete runtime state of a `Granulator`, see `Granulator::snapshot`.
+ */
+pub struct GranulatorState<T = f32, const N: usize = 2>(Granulator<T, N>);
 
-        Granulator {
-            scheduler: Scheduler::new(density),
-            grains_pool: [Grain::new(position, duration, pitch); MAX_GRAINS],
-            delay_line,
-            position,
-            duration,
-            pitch,
-            volume,
-            feedback,
-            wet_dry,
-            new_grain_hook,
+impl<T: Sample, const N: usize> Clone for GranulatorState<T, N> {
+    fn clone(&self) -> Self {
+        GranulatorState(self.0.clone())
+    }
+}
 
-            freeverb: Freeverb::new(DEFAULT_SAMPLE_RATE),
+// `clone_from` reuses the buffers, so that restoring a state doesn't allocate.
+impl<T: Sample, const N: usize> Clone for Granulator<T, N> {
+    fn clone(&self) -> Self {
+        let mut pending_grains = Vec::with_capacity(MAX_PENDING_GRAINS);
+        pending_grains.extend_from_slice(&self.pending_grains);
+        Granulator {
+            scheduler: self.scheduler.clone(),
+            onset_detector: self.onset_detector.clone(),
+            burst: self.burst.clone(),
+            looper: self.looper.clone(),
+            grains_pool: self.grains_pool.clone(),
+            spectral: self.spectral.clone(),
+            delay_line: self.delay_line.clone(),
+            feedback_processor: self.feedback_processor.clone(),
+            mixer: self.mixer.clone(),
+            diffuser: self.diffuser.clone(),
+            reverb: self.reverb.clone(),
+            morph: self.morph.clone(),
+            envelope_follower: self.envelope_follower.clone(),
+            rng: self.rng.clone(),
+            grain_parameter_source: None,
+            pending_grains,
+            ..*self
         }
     }
-    pub fn process(&mut self, input_frame: Frame) -> Frame {
-        let should_start_new_grain = self.scheduler.advance();
-        if should_start_new_grain {
-            self.activate_grain();
-            match &self.new_grain_hook {
-                Some(new_grain_hook) => new_grain_hook(self.duration),
-                None => {}
+
+    fn clone_from(&mut self, source: &Self) {
+        self.mode = source.mode;
+        self.scheduler.clone_from(&source.scheduler);
+        self.grain_trigger = source.grain_trigger;
+        self.onset_detector.clone_from(&source.onset_detector);
+        self.onset_burst = source.onset_burst;
+        self.burst.clone_from(&source.burst);
+        self.sequencer = source.sequencer;
+        self.looper.clone_from(&source.looper);
+        self.spectral.clone_from(&source.spectral);
+        self.grains_pool.clone_from(&source.grains_pool);
+        self.delay_line.clone_from(&source.delay_line);
+        self.position = source.position;
+        self.duration = source.duration;
+        self.pitch = source.pitch;
+        self.volume = source.volume;
+        self.feedback = source.feedback;
+        self.feedback_processor
+            .clone_from(&source.feedback_processor);
+        self.mixer.clone_from(&source.mixer);
+        self.dry_latency_compensation = source.dry_latency_compensation;
+        self.new_grain_hook = source.new_grain_hook;
+        self.diffuser.clone_from(&source.diffuser);
+        self.reverb_routing = source.reverb_routing;
+        self.grain_spread = source.grain_spread;
+        self.zero_crossing_window = source.zero_crossing_window;
+        self.grain_filter = source.grain_filter;
+        self.grain_filter_cutoff = source.grain_filter_cutoff;
+        self.grain_filter_cutoff_spread = source.grain_filter_cutoff_spread;
+        self.grain_filter_resonance = source.grain_filter_resonance;
+        self.grain_filter_resonance_spread = source.grain_filter_resonance_spread;
+        self.grain_filter_sweep = source.grain_filter_sweep;
+        self.reverb.clone_from(&source.reverb);
+        self.morph.clone_from(&source.morph);
+        self.pending_grains.clone_from(&source.pending_grains);
+        self.envelope_follower.clone_from(&source.envelope_follower);
+        self.envelope = source.envelope;
+        self.envelope_to_density = source.envelope_to_density;
+        self.envelope_to_volume = source.envelope_to_volume;
+        self.envelope_to_feedback = source.envelope_to_feedback;
+        self.envelope_to_position = source.envelope_to_position;
+        self.rng.clone_from(&source.rng);
+        self.parameters = source.parameters;
+        self.sample_rate = source.sample_rate;
+    }
+}
+
+impl<T: Sample, const N: usize> Granulator<T, N> {
+    pub fn new(options: GranulatorOptions) -> Granulator<T, N> {
+        Granulator::with_sample_rate(options, DEFAULT_SAMPLE_RATE)
+    }
+
+    /**
+     * Positions and durations in `options` are in samples at `sample_rate`.
+     */
+    pub fn with_sample_rate(options: GranulatorOptions, sample_rate: usize) -> Granulator<T, N> {
+        let mut granulator = Granulator {
+            mode: options.mode,
+            scheduler: Scheduler::new(options.density),
+            grain_trigger: options.grain_trigger,
+            onset_detector: OnsetDetector::new(sample_rate as f32),
+            onset_burst: options.onset_burst,
+            burst: Burst::new(),
+            sequencer: Sequencer::new(sample_rate as f32),
+            looper: Looper::new(),
+            spectral: Spectral::new(options.density),
+            grains_pool: GrainPool::new(DEFAULT_MAX_GRAINS),
+            delay_line: DelayLine::new(MAX_DELAY_TIME_SECONDS * sample_rate),
+            position: options.position,
+            duration: options.duration,
+            pitch: options.pitch,
+            volume: options.volume,
+            feedback: options.feedback,
+            feedback_processor: FeedbackProcessor::new(sample_rate as f32),
+            mixer: Mixer::new(spectral::LATENCY),
+            dry_latency_compensation: options.dry_latency_compensation,
+            new_grain_hook: options.new_grain_hook,
+            grain_parameter_source: None,
+            pending_grains: Vec::with_capacity(MAX_PENDING_GRAINS),
+            diffuser: Diffuser::new(sample_rate as f32),
+            grain_spread: options.grain_spread,
+            zero_crossing_window: 0,
+            grain_filter: options.grain_filter,
+            grain_filter_cutoff: options.grain_filter_cutoff,
+            grain_filter_cutoff_spread: options.grain_filter_cutoff_spread,
+            grain_filter_resonance: options.grain_filter_resonance,
+            grain_filter_resonance_spread: options.grain_filter_resonance_spread,
+            grain_filter_sweep: options.grain_filter_sweep,
+            reverb_routing: options.reverb_routing,
+            reverb: Reverb::new(sample_rate),
+            morph: None,
+            envelope_follower: EnvelopeFollower::new(sample_rate as f32),
+            envelope: 0.0,
+            envelope_to_density: options.envelope_to_density,
+            envelope_to_volume: options.envelope_to_volume,
+            envelope_to_feedback: options.envelope_to_feedback,
+            envelope_to_position: options.envelope_to_position,
+            rng: StdRng::from_entropy(),
+            parameters: [0.0; NUM_PARAMETERS],
+            sample_rate,
+        };
+        granulator.set_options(&options);
+
+        granulator
+    }
+
+    /**
+     * Applies all parameters from options, the new grain hook is left as is.
+     */
+    pub fn set_options(&mut self, options: &GranulatorOptions) {
+        self.set_mode(options.mode);
+        self.set_position(options.position);
+        self.set_density(options.density);
+        self.set_overlap(options.overlap);
+        self.set_grain_trigger(options.grain_trigger);
+        self.set_onset_sensitivity(options.onset_sensitivity);
+        self.set_onset_refractory(options.onset_refractory);
+        self.set_onset_burst(options.onset_burst);
+        self.set_tempo(options.tempo);
+        self.set_pattern_division(options.pattern_division);
+        self.set_duration(options.duration);
+        self.set_pitch(options.pitch);
+        self.set_grain_spread(options.grain_spread);
+        self.set_zero_crossing_window(options.zero_crossing_window);
+        self.set_grain_filter(options.grain_filter);
+        self.set_grain_filter_cutoff(options.grain_filter_cutoff);
+        self.set_grain_filter_cutoff_spread(options.grain_filter_cutoff_spread);
+        self.set_grain_filter_resonance(options.grain_filter_resonance);
+        self.set_grain_filter_resonance_spread(options.grain_filter_resonance_spread);
+        self.set_grain_filter_sweep(options.grain_filter_sweep);
+        self.set_volume(options.volume);
+        self.set_feedback(options.feedback);
+        self.set_feedback_high_pass(options.feedback_high_pass);
+        self.set_feedback_low_pass(options.feedback_low_pass);
+        self.set_feedback_dc_blocker(options.feedback_dc_blocker);
+        self.set_feedback_saturation(options.feedback_saturation);
+        self.set_wet_dry(options.wet_dry);
+        self.set_crossfade_law(options.crossfade_law);
+        self.set_dry_level(options.dry_level);
+        self.set_wet_level(options.wet_level);
+        self.set_dry_latency_compensation(options.dry_latency_compensation);
+        self.set_diffusion(options.diffusion);
+        self.set_reverb_routing(options.reverb_routing);
+        self.set_reverb_amount(options.reverb_amount);
+        self.set_reverb_room_size(options.reverb_room_size);
+        self.set_reverb_damping(options.reverb_damping);
+        self.set_reverb_width(options.reverb_width);
+        self.set_reverb_freeze(options.reverb_freeze);
+        self.set_spectral_freeze(options.spectral_freeze);
+        self.set_spectral_phase_randomization(options.spectral_phase_randomization);
+        self.set_spectral_quantization(options.spectral_quantization);
+        self.set_envelope_attack(options.envelope_attack);
+        self.set_envelope_release(options.envelope_release);
+        self.set_envelope_gain(options.envelope_gain);
+        self.set_envelope_to_density(options.envelope_to_density);
+        self.set_envelope_to_volume(options.envelope_to_volume);
+        self.set_envelope_to_feedback(options.envelope_to_feedback);
+        self.set_envelope_to_position(options.envelope_to_position);
+        self.set_morph_position(options.morph_x, options.morph_y);
+    }
+
+    /**
+     * Current value of all parameters.
+     */
+    pub fn options(&self) -> GranulatorOptions {
+        let is_on = |id: ParameterId| self.get_parameter(id) >= 0.5;
+        let optional = |id: ParameterId| {
+            let plain = self.get_parameter(id);
+            if id.descriptor().is_off(plain) {
+                None
+            } else {
+                Some(plain)
             }
+        };
+        let index = |id: ParameterId| self.get_parameter(id) as usize;
+        let samples = |id: ParameterId| {
+            units::milliseconds_to_samples(self.get_parameter(id), self.sample_rate)
+        };
+
+        GranulatorOptions {
+            mode: Mode::from_index(index(ParameterId::Mode)),
+            position: samples(ParameterId::Position),
+            density: self.get_parameter(ParameterId::Density),
+            overlap: optional(ParameterId::Overlap),
+            grain_trigger: GrainTrigger::from_index(index(ParameterId::GrainTrigger)),
+            onset_sensitivity: self.get_parameter(ParameterId::OnsetSensitivity),
+            onset_refractory: self.get_parameter(ParameterId::OnsetRefractory),
+            onset_burst: index(ParameterId::OnsetBurst),
+            tempo: self.get_parameter(ParameterId::Tempo),
+            pattern_division: index(ParameterId::PatternDivision),
+            duration: samples(ParameterId::Duration),
+            pitch: self.get_parameter(ParameterId::Pitch),
+            grain_spread: self.get_parameter(ParameterId::GrainSpread),
+            zero_crossing_window: self.get_parameter(ParameterId::ZeroCrossingWindow),
+            grain_filter: GrainFilter::from_index(index(ParameterId::GrainFilter)),
+            grain_filter_cutoff: self.get_parameter(ParameterId::GrainFilterCutoff),
+            grain_filter_cutoff_spread: self.get_parameter(ParameterId::GrainFilterCutoffSpread),
+            grain_filter_resonance: self.get_parameter(ParameterId::GrainFilterResonance),
+            grain_filter_resonance_spread: self
+                .get_parameter(ParameterId::GrainFilterResonanceSpread),
+            grain_filter_sweep: self.get_parameter(ParameterId::GrainFilterSweep),
+            volume: self.get_parameter(ParameterId::Volume),
+            feedback: self.get_parameter(ParameterId::Feedback),
+            feedback_high_pass: optional(ParameterId::FeedbackHighPass),
+            feedback_low_pass: optional(ParameterId::FeedbackLowPass),
+            feedback_dc_blocker: is_on(ParameterId::FeedbackDcBlocker),
+            feedback_saturation: optional(ParameterId::FeedbackSaturation),
+            wet_dry: self.get_parameter(ParameterId::WetDry),
+            crossfade_law: CrossfadeLaw::from_index(index(ParameterId::CrossfadeLaw)),
+            dry_level: self.get_parameter(ParameterId::DryLevel),
+            wet_level: self.get_parameter(ParameterId::WetLevel),
+            dry_latency_compensation: is_on(ParameterId::DryLatencyCompensation),
+            diffusion: self.get_parameter(ParameterId::Diffusion),
+            reverb_routing: ReverbRouting::from_index(index(ParameterId::ReverbRouting)),
+            reverb_amount: self.get_parameter(ParameterId::ReverbAmount),
+            reverb_room_size: self.get_parameter(ParameterId::ReverbRoomSize),
+            reverb_damping: self.get_parameter(ParameterId::ReverbDamping),
+            reverb_width: self.get_parameter(ParameterId::ReverbWidth),
+            reverb_freeze: is_on(ParameterId::ReverbFreeze),
+            spectral_freeze: is_on(ParameterId::SpectralFreeze),
+            spectral_phase_randomization: self
+                .get_parameter(ParameterId::SpectralPhaseRandomization),
+            spectral_quantization: self.get_parameter(ParameterId::SpectralQuantization),
+            envelope_attack: self.get_parameter(ParameterId::EnvelopeAttack),
+            envelope_release: self.get_parameter(ParameterId::EnvelopeRelease),
+            envelope_gain: self.get_parameter(ParameterId::EnvelopeGain),
+            envelope_to_density: self.get_parameter(ParameterId::EnvelopeToDensity),
+            envelope_to_volume: self.get_parameter(ParameterId::EnvelopeToVolume),
+            envelope_to_feedback: self.get_parameter(ParameterId::EnvelopeToFeedback),
+            envelope_to_position: self.get_parameter(ParameterId::EnvelopeToPosition),
+            morph_x: self.get_parameter(ParameterId::MorphX),
+            morph_y: self.get_parameter(ParameterId::MorphY),
+            new_grain_hook: self.new_grain_hook,
         }
+    }
 
-        let synthesized_frame = self.synthesize_active_grains();
-        let feedback_frame = self.get_feedback_frame(input_frame, synthesized_frame);
+    /**
+     * Silences the engine: clears the delay line, stops all grains and
+     * flushes the reverb and filters. Parameters are kept, nothing is allocated.
+     */
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.grains_pool.reset();
+        self.scheduler.reset();
+        self.onset_detector.reset();
+        self.burst.reset();
+        self.sequencer.reset();
+        self.looper.reset();
+        self.spectral.reset();
+        self.feedback_processor.reset();
+        self.mixer.reset();
+        self.diffuser.reset();
+        self.reverb.reset();
+        self.envelope_follower.reset();
+        self.envelope = 0.0;
+        self.pending_grains.clear();
+    }
 
-        self.delay_line.write_and_advance(feedback_frame);
+    /**
+     * Copy of the complete runtime state, parameters included: delay line,
+     * grains, scheduler, reverb and random number generator.
+     */
+    pub fn snapshot(&self) -> GranulatorState<T, N> {
+        GranulatorState(self.clone())
+    }
 
-        let output_frame = self.get_output_frame(input_frame, synthesized_frame);
-        let processed = self.freeverb.tick((output_frame[0], output_frame[1]));
+    /**
+     * Returns to a state taken with `snapshot`, without allocating.
+     * Processing then continues exactly as it did after the snapshot.
+     */
+    pub fn restore(&mut self, state: &GranulatorState<T, N>) {
+        self.clone_from(&state.0);
+    }
 
-        [processed.0, processed.1]
+    /**
+     * Makes the random choices repeatable, for offline renders and tests.
+     */
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn process(&mut self, input_frame: Frame<T, N>) -> Frame<T, N> {
+        self.process_with_sidechain(input_frame, input_frame)
     }
 
-    fn get_output_frame(
+    /**
+     * Cross-granulation: grains are read from the sidechain recorded in the
+     * delay line, while the input forms the dry signal and drives the onset
+     * detector and the envelope follower.
+     */
+    pub fn process_with_sidechain(
         &mut self,
-        [input_left, input_right]: Frame,
-        [synthesized_left, synthesized_right]: Frame,
-    ) -> Frame {
-        let dry = 1.0 - self.wet_dry;
+        input_frame: Frame<T, N>,
+        sidechain_frame: Frame<T, N>,
+    ) -> Frame<T, N> {
+        self.envelope = self.envelope_follower.process(input_frame);
+        self.scheduler.set_rate(follower::modulation(
+            self.envelope_to_density,
+            self.envelope,
+        ));
+
+        let position = T::from_f32(self.position);
+        let duration = T::from_f32(self.duration);
+        let pitch = T::from_f32(self.pitch);
+        let synthesized_frame = match self.mode {
+            Mode::Cloud => self.synthesize_cloud(input_frame),
+            Mode::Loop => self
+                .looper
+                .process(&self.delay_line, position, duration, pitch),
+            Mode::Spectral => {
+                let position = T::from_f32(self.modulated_position());
+                self.spectral
+                    .process(&self.delay_line, position, duration, pitch, &mut self.rng)
+            }
+        };
+        let synthesized_frame = self.diffuser.process(synthesized_frame);
+        let feedback_frame = self.get_feedback_frame(sidechain_frame, synthesized_frame);
+
+        self.delay_line.write_and_advance(feedback_frame);
+
+        let wet_frame = match self.reverb_routing {
+            ReverbRouting::Grains => self.process_reverb(synthesized_frame),
+            _ => synthesized_frame,
+        };
+        let output_frame = self.get_output_frame(input_frame, wet_frame);
 
-        [
-            (-input_left * dry + synthesized_left * self.wet_dry) * self.volume,
-            (-input_right * dry + synthesized_right * self.wet_dry) * self.volume,
-        ]
+        match self.reverb_routing {
+            ReverbRouting::Output => self.process_reverb(output_frame),
+            _ => output_frame,
+        }
     }
 
-    fn get_feedback_frame(
-        &mut self,
-        [input_left, input_right]: Frame,
-        [synthesized_left, synthesized_right]: Frame,
-    ) -> Frame {
-        [
-            input_left + synthesized_left * self.feedback,
-            input_right + synthesized_right * self.feedback,
-        ]
+    fn process_reverb(&mut self, frame: Frame<T, N>) -> Frame<T, N> {
+        self.reverb.process(frame)
+    }
+
+    fn get_output_frame(&mut self, input: Frame<T, N>, synthesized: Frame<T, N>) -> Frame<T, N> {
+        let modulation = follower::modulation(self.envelope_to_volume, self.envelope);
+        let volume = T::from_f32(self.volume * modulation);
+        let mut frame = self.mixer.process(input, synthesized);
+        for sample in frame.iter_mut() {
+            *sample *= volume;
+        }
+        frame
     }
 
     /**
-     * Mix output samples of currently active grains.
+     * Processing latency of the current mode, in samples, on top of `position`.
      */
-    fn synthesize_active_grains(&mut self) -> Frame {
-        let mut num_active_grains: f32 = 0.0;
-        let [mut left, mut right]: Frame = SILENT_FRAME;
-        let gain: f32 = 2.0;
+    pub fn latency(&self) -> usize {
+        match self.mode {
+            Mode::Spectral => spectral::LATENCY,
+            Mode::Cloud | Mode::Loop => 0,
+        }
+    }
+
+    fn update_dry_delay(&mut self) {
+        let dry_delay = if self.dry_latency_compensation {
+            self.latency()
+        } else {
+            0
+        };
+        self.mixer.set_dry_delay(dry_delay);
+    }
+
+    fn get_feedback_frame(&mut self, input: Frame<T, N>, synthesized: Frame<T, N>) -> Frame<T, N> {
+        let processed = self.feedback_processor.process(synthesized);
+        let processed = match self.reverb_routing {
+            ReverbRouting::Feedback => self.process_reverb(processed),
+            _ => processed,
+        };
+
+        let modulation = follower::modulation(self.envelope_to_feedback, self.envelope);
+        let feedback = T::from_f32(self.feedback * modulation);
+        let mut frame = input;
+        for (sample, processed) in frame.iter_mut().zip(processed.iter()) {
+            *sample += *processed * feedback;
+        }
+        frame
+    }
+
+    fn synthesize_cloud(&mut self, input_frame: Frame<T, N>) -> Frame<T, N> {
+        let scheduled = match self.grain_trigger {
+            GrainTrigger::Scheduler | GrainTrigger::SchedulerAndOnsets => {
+                self.scheduler.advance(&mut self.rng)
+            }
+            GrainTrigger::Onsets | GrainTrigger::Pattern => None,
+        };
+        let is_onset = match self.grain_trigger {
+            GrainTrigger::SchedulerAndOnsets | GrainTrigger::Onsets => {
+                self.onset_detector.process(input_frame)
+            }
+            GrainTrigger::Scheduler | GrainTrigger::Pattern => false,
+        };
+        let pattern_step = match self.grain_trigger {
+            GrainTrigger::Pattern => self.sequencer.advance(&mut self.rng),
+            _ => None,
+        };
+        if is_onset {
+            let grain_length = self.duration / self.pitch.abs().max(f32::EPSILON);
+            self.burst
+                .start(self.onset_burst, grain_length / self.onset_burst as f32);
+        }
 
-        for grain in self.grains_pool.iter_mut() {
-            if grain.is_active == true {
-                let [left_grain, right_grain] = grain.process(&self.delay_line);
-                left += left_grain;
-                right += right_grain;
-                num_active_grains += 1.0;
+        if let Some(lateness) = self.burst.advance() {
+            self.activate_grain(PatternStep::HIT, lateness);
+        }
+        if let Some(lateness) = scheduled {
+            self.activate_grain(PatternStep::HIT, lateness);
+        }
+        if let Some((step, lateness)) = pattern_step {
+            self.activate_grain(step, lateness);
+        }
+
+        let mut index = 0;
+        while index < self.pending_grains.len() {
+            if self.pending_grains[index].0 == 0 {
+                let (_, parameters) = self.pending_grains.swap_remove(index);
+                self.start_grain(&parameters, 0.0);
+            } else {
+                self.pending_grains[index].0 -= 1;
+                index += 1;
             }
         }
 
-        if num_active_grains > 0.0 {
-            return [
-                left / num_active_grains * gain,
-                right / num_active_grains * gain,
-            ];
+        self.synthesize_active_grains()
+    }
+
+    /**
+     * Mix output samples of currently active grains.
+     */
+    fn synthesize_active_grains(&mut self) -> Frame<T, N> {
+        let gain = T::from_f32(2.0);
+        let (mut frame, num_active_grains) = self.grains_pool.process(&self.delay_line);
+
+        if num_active_grains > 0 {
+            let num_active_grains = T::from_usize(num_active_grains);
+            for sample in frame.iter_mut() {
+                *sample = *sample / num_active_grains * gain;
+            }
         }
 
-        SILENT_FRAME
+        frame
     }
 
     /**
-     * Active one grain from the grains pool if available.
+     * Active one grain from the grains pool if available, with the offsets
+     * of a pattern step. `lateness` is the fraction of a sample by which
+     * its onset precedes the current sample.
      */
-    fn activate_grain(&mut self) {
-        for grain in self.grains_pool.iter_mut() {
-            if grain.is_active == false {
-                grain.activate(self.position, self.duration, self.pitch);
-                continue;
+    fn activate_grain(&mut self, step: PatternStep, lateness: f32) {
+        let pan = self.rng.gen_range(0.0..N as f32);
+        let mut parameters = GrainParameters {
+            position: self.modulated_position() + step.position,
+            duration: self.duration,
+            pitch: self.pitch * units::semitones_to_ratio(step.pitch),
+            pan: step.pan.unwrap_or(pan),
+            amplitude: 1.0,
+            envelope: GrainEnvelope::Parabolic,
+        };
+        if let Some(source) = &mut self.grain_parameter_source {
+            let defaults = parameters;
+            parameters = source.next_grain(defaults);
+            if !parameters.is_playable() {
+                parameters = defaults;
             }
         }
+        self.start_grain(&parameters, lateness);
+    }
+
+    fn start_grain(&mut self, parameters: &GrainParameters, lateness: f32) {
+        let mut parameters = *parameters;
+        if self.zero_crossing_window > 0 {
+            parameters.position = self
+                .delay_line
+                .quietest_delay(T::from_f32(parameters.position), self.zero_crossing_window)
+                .to_parameter();
+        }
+        let filter = self.voice_filter();
+        self.grains_pool.activate(
+            &parameters,
+            T::from_f32(self.grain_spread),
+            filter,
+            T::from_f32(lateness),
+        );
+        if let Some(new_grain_hook) = &self.new_grain_hook {
+            new_grain_hook(parameters.duration);
+        }
+    }
+
+    /**
+     * Starts a grain with exactly these parameters `offset` samples into
+     * the next call to `process`, or the next block of calls, so that
+     * sequencers and MIDI notes can place grains precisely. Cloud mode
+     * only, alongside the scheduler and onsets. Returns false in other
+     * modes, when `MAX_PENDING_GRAINS` grains are already waiting or when
+     * the parameters are not playable, see `GrainParameters::is_playable`.
+     */
+    pub fn trigger_grain(&mut self, parameters: GrainParameters, offset: usize) -> bool {
+        if self.mode != Mode::Cloud
+            || self.pending_grains.len() == MAX_PENDING_GRAINS
+            || !parameters.is_playable()
+        {
+            return false;
+        }
+        self.pending_grains.push((offset, parameters));
+        true
+    }
+
+    /**
+     * Decides the parameters of each new grain in cloud mode, `None` goes
+     * back to the usual behaviour. The source belongs to the caller rather
+     * than to the engine state: `restore` keeps the current one, snapshots
+     * and clones are made without it.
+     */
+    pub fn set_grain_parameter_source(&mut self, source: Option<Box<dyn GrainParameterSource>>) {
+        self.grain_parameter_source = source;
+    }
+
+    /**
+     * Filter of a new grain, with its own random cutoff and resonance.
+     */
+    fn voice_filter(&mut self) -> VoiceFilter<T> {
+        if self.grain_filter == GrainFilter::Off {
+            return VoiceFilter::default();
+        }
+        let cutoff_offset = self.grain_filter_cutoff_spread * self.rng.gen_range(-1.0..1.0);
+        let resonance_offset =
+            2.0 * self.grain_filter_resonance_spread * self.rng.gen_range(-1.0..1.0);
+        VoiceFilter::new(
+            self.grain_filter_cutoff * cutoff_offset.exp2(),
+            self.grain_filter_resonance * resonance_offset.exp2(),
+            self.grain_filter_sweep,
+            self.sample_rate as f32,
+        )
+    }
+
+    /**
+     * Maximum number of simultaneous grains, up to `MAX_POLYPHONY`.
+     * Allocates, so it should not be called from the audio thread.
+     */
+    pub fn set_max_grains(&mut self, max_grains: usize) {
+        self.grains_pool
+            .set_capacity(max_grains.clamp(1, MAX_POLYPHONY));
+    }
+
+    pub fn max_grains(&self) -> usize {
+        self.grains_pool.capacity()
+    }
+
+    /**
+     * Number of grains currently playing.
+     */
+    pub fn num_active_grains(&self) -> usize {
+        self.grains_pool.num_active()
+    }
+
+    /**
+     * Leaving cloud mode drops the grains waiting from `trigger_grain`.
+     */
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        if mode != Mode::Cloud {
+            self.pending_grains.clear();
+        }
+        self.update_dry_delay();
+        self.store_parameter(ParameterId::Mode, mode as usize as f32);
     }
 
     pub fn set_position(&mut self, position: Position) {
         self.position = position;
+        let milliseconds = units::samples_to_milliseconds(position, self.sample_rate);
+        self.store_parameter(ParameterId::Position, milliseconds);
+    }
+
+    pub fn set_position_milliseconds(&mut self, milliseconds: f32) {
+        self.set_position(units::milliseconds_to_samples(
+            milliseconds,
+            self.sample_rate,
+        ));
+    }
+
+    pub fn set_position_seconds(&mut self, seconds: f32) {
+        self.set_position_milliseconds(seconds * 1000.0);
+    }
+
+    /**
+     * 0.0 - 1.0, from the write head to the oldest sample of the delay line.
+     */
+    pub fn set_position_fraction(&mut self, fraction: f32) {
+        self.set_position(fraction.clamp(0.0, 1.0) * self.max_delay() as f32);
+    }
+
+    pub fn position_milliseconds(&self) -> f32 {
+        units::samples_to_milliseconds(self.position, self.sample_rate)
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /**
+     * Length of the delay line, in samples.
+     */
+    pub fn max_delay(&self) -> usize {
+        MAX_DELAY_TIME_SECONDS * self.sample_rate
     }
 
     pub fn set_density(&mut self, density: Density) {
+        self.spectral.set_density(density);
+        self.store_parameter(ParameterId::Density, density);
+        self.update_scheduler_density();
+    }
+
+    /**
+     * Average number of overlapping grains, `None` goes back to `density`.
+     */
+    pub fn set_overlap(&mut self, overlap: Option<f32>) {
+        self.store_optional_parameter(ParameterId::Overlap, overlap);
+        self.update_scheduler_density();
+    }
+
+    pub fn set_grain_trigger(&mut self, grain_trigger: GrainTrigger) {
+        self.grain_trigger = grain_trigger;
+        self.store_parameter(ParameterId::GrainTrigger, grain_trigger as usize as f32);
+    }
+
+    /**
+     * 0.0 - 1.0, higher values detect softer transients.
+     */
+    pub fn set_onset_sensitivity(&mut self, sensitivity: f32) {
+        self.onset_detector.set_sensitivity(sensitivity);
+        self.store_parameter(ParameterId::OnsetSensitivity, sensitivity);
+    }
+
+    /**
+     * Time after a transient during which no other is detected, in milliseconds.
+     */
+    pub fn set_onset_refractory(&mut self, milliseconds: f32) {
+        let samples = units::milliseconds_to_samples(milliseconds, self.sample_rate);
+        self.onset_detector
+            .set_refractory_samples(samples.max(0.0) as usize);
+        self.store_parameter(ParameterId::OnsetRefractory, milliseconds);
+    }
+
+    /**
+     * Number of grains started by each transient, spread over one grain length.
+     */
+    pub fn set_onset_burst(&mut self, num_grains: usize) {
+        self.onset_burst = num_grains.max(1);
+        self.store_parameter(ParameterId::OnsetBurst, self.onset_burst as f32);
+    }
+
+    /**
+     * Step sequence played with `GrainTrigger::Pattern`: a Euclidean rhythm
+     * or per-step probabilities, with per-step pitch, position and pan offsets.
+     */
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        self.sequencer.set_pattern(pattern);
+    }
+
+    pub fn pattern(&self) -> &Pattern {
+        self.sequencer.pattern()
+    }
+
+    /**
+     * Tempo of the pattern clock, in beats per minute.
+     */
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.sequencer.set_tempo(tempo);
+        self.store_parameter(ParameterId::Tempo, tempo);
+    }
+
+    /**
+     * Pattern steps per beat, 4 plays sixteenth notes.
+     */
+    pub fn set_pattern_division(&mut self, division: usize) {
+        let division = division.max(1);
+        self.sequencer.set_division(division as f32);
+        self.store_parameter(ParameterId::PatternDivision, division as f32);
+    }
+
+    fn update_scheduler_density(&mut self) {
+        let overlap = self.get_parameter(ParameterId::Overlap);
+        let density = if ParameterId::Overlap.descriptor().is_off(overlap) {
+            self.get_parameter(ParameterId::Density)
+        } else {
+            let pitch = self.pitch.abs().max(ParameterId::Pitch.descriptor().min);
+            let grain_length = self.duration / pitch;
+            Scheduler::density_for_interonset(grain_length / overlap)
+        };
         self.scheduler.set_density(density);
     }
 
     pub fn set_duration(&mut self, duration: Duration) {
         self.duration = duration;
+        let milliseconds = units::samples_to_milliseconds(duration, self.sample_rate);
+        self.store_parameter(ParameterId::Duration, milliseconds);
+        self.update_scheduler_density();
+    }
+
+    pub fn set_duration_milliseconds(&mut self, milliseconds: f32) {
+        self.set_duration(units::milliseconds_to_samples(
+            milliseconds,
+            self.sample_rate,
+        ));
     }
+
+    pub fn duration_milliseconds(&self) -> f32 {
+        units::samples_to_milliseconds(self.duration, self.sample_rate)
+    }
+
     pub fn set_volume(&mut self, volume: Volume) {
         self.volume = volume;
+        self.store_parameter(ParameterId::Volume, volume);
     }
     pub fn set_new_grain_hook(&mut self, new_grain_hook: Option<NewGrainHook>) {
         self.new_grain_hook = new_grain_hook;
@@ -193,13 +1216,1062 @@ impl Granulator {
 
     pub fn set_feedback(&mut self, feedback: Feedback) {
         self.feedback = feedback;
+        self.store_parameter(ParameterId::Feedback, feedback);
+    }
+
+    pub fn set_feedback_high_pass(&mut self, cutoff: Option<Frequency>) {
+        self.feedback_processor.set_high_pass(cutoff);
+        self.store_optional_parameter(ParameterId::FeedbackHighPass, cutoff);
+    }
+
+    pub fn set_feedback_low_pass(&mut self, cutoff: Option<Frequency>) {
+        self.feedback_processor.set_low_pass(cutoff);
+        self.store_optional_parameter(ParameterId::FeedbackLowPass, cutoff);
+    }
+
+    pub fn set_feedback_dc_blocker(&mut self, is_enabled: bool) {
+        self.feedback_processor.set_dc_blocker(is_enabled);
+        self.store_parameter(ParameterId::FeedbackDcBlocker, is_enabled as u8 as f32);
+    }
+
+    pub fn set_feedback_saturation(&mut self, drive: Option<Drive>) {
+        self.feedback_processor.set_saturation(drive);
+        self.store_optional_parameter(ParameterId::FeedbackSaturation, drive);
+    }
+
+    pub fn set_diffusion(&mut self, diffusion: Diffusion) {
+        self.diffuser.set_amount(diffusion);
+        self.store_parameter(ParameterId::Diffusion, diffusion);
+    }
+
+    pub fn set_reverb_routing(&mut self, reverb_routing: ReverbRouting) {
+        self.reverb_routing = reverb_routing;
+        self.store_parameter(ParameterId::ReverbRouting, reverb_routing as usize as f32);
+    }
+
+    pub fn set_reverb_amount(&mut self, reverb_amount: ReverbAmount) {
+        self.reverb.set_wet(reverb_amount);
+        self.reverb.set_dry(1.0 - reverb_amount);
+        self.store_parameter(ParameterId::ReverbAmount, reverb_amount);
+    }
+
+    pub fn set_reverb_room_size(&mut self, room_size: f32) {
+        self.reverb.set_room_size(room_size);
+        self.store_parameter(ParameterId::ReverbRoomSize, room_size);
+    }
+
+    pub fn set_reverb_damping(&mut self, damping: f32) {
+        self.reverb.set_dampening(damping);
+        self.store_parameter(ParameterId::ReverbDamping, damping);
+    }
+
+    pub fn set_reverb_width(&mut self, width: f32) {
+        self.reverb.set_width(width);
+        self.store_parameter(ParameterId::ReverbWidth, width);
+    }
+
+    pub fn set_reverb_freeze(&mut self, is_frozen: bool) {
+        self.reverb.set_freeze(is_frozen);
+        self.store_parameter(ParameterId::ReverbFreeze, is_frozen as u8 as f32);
     }
 
     pub fn set_wet_dry(&mut self, wet_dry: WetDry) {
-        self.wet_dry = wet_dry;
+        self.mixer.set_wet_dry(wet_dry);
+        self.store_parameter(ParameterId::WetDry, wet_dry);
+    }
+
+    pub fn set_crossfade_law(&mut self, crossfade_law: CrossfadeLaw) {
+        self.mixer.set_law(crossfade_law);
+        self.store_parameter(ParameterId::CrossfadeLaw, crossfade_law as usize as f32);
+    }
+
+    pub fn set_dry_level(&mut self, dry_level: Level) {
+        self.mixer.set_dry_level(dry_level);
+        self.store_parameter(ParameterId::DryLevel, dry_level);
+    }
+
+    pub fn set_wet_level(&mut self, wet_level: Level) {
+        self.mixer.set_wet_level(wet_level);
+        self.store_parameter(ParameterId::WetLevel, wet_level);
+    }
+
+    pub fn set_dry_latency_compensation(&mut self, is_enabled: bool) {
+        self.dry_latency_compensation = is_enabled;
+        self.update_dry_delay();
+        self.store_parameter(ParameterId::DryLatencyCompensation, is_enabled as u8 as f32);
     }
 
     pub fn set_pitch(&mut self, pitch: Pitch) {
         self.pitch = pitch;
+        self.store_parameter(ParameterId::Pitch, pitch);
+        self.update_scheduler_density();
+    }
+
+    /**
+     * Transposition, 0.0 plays at the original pitch.
+     */
+    pub fn set_pitch_semitones(&mut self, semitones: f32, cents: f32) {
+        self.set_pitch(units::semitones_to_ratio(semitones + cents / 100.0));
+    }
+
+    /**
+     * 1 V/oct control voltage, 0.0 V plays at the original pitch.
+     */
+    pub fn set_pitch_volts(&mut self, volts: f32) {
+        self.set_pitch(units::volts_to_ratio(volts));
+    }
+
+    pub fn pitch_semitones(&self) -> f32 {
+        units::ratio_to_semitones(self.pitch)
+    }
+
+    /**
+     * Placement of new grains across the output channels, 0.0 - 1.0
+     */
+    pub fn set_grain_spread(&mut self, grain_spread: f32) {
+        self.grain_spread = grain_spread;
+        self.store_parameter(ParameterId::GrainSpread, grain_spread);
+    }
+
+    /**
+     * Largest shift of each grain's start towards a zero crossing, in
+     * milliseconds. 0.0 turns the search off.
+     */
+    pub fn set_zero_crossing_window(&mut self, milliseconds: f32) {
+        let samples = units::milliseconds_to_samples(milliseconds, self.sample_rate);
+        self.zero_crossing_window = samples.round().max(0.0) as usize;
+        self.store_parameter(ParameterId::ZeroCrossingWindow, milliseconds);
+    }
+
+    /**
+     * Filter of new grains. Playing grains keep their cutoff and resonance.
+     */
+    pub fn set_grain_filter(&mut self, grain_filter: GrainFilter) {
+        self.grain_filter = grain_filter;
+        self.grains_pool.set_filter_mode(grain_filter.filter_mode());
+        self.store_parameter(ParameterId::GrainFilter, grain_filter as usize as f32);
+    }
+
+    /**
+     * Base cutoff of the grain filter, in Hz.
+     */
+    pub fn set_grain_filter_cutoff(&mut self, cutoff: f32) {
+        self.grain_filter_cutoff = cutoff;
+        self.store_parameter(ParameterId::GrainFilterCutoff, cutoff);
+    }
+
+    /**
+     * Random cutoff offset of each grain, up to `octaves` up or down.
+     */
+    pub fn set_grain_filter_cutoff_spread(&mut self, octaves: f32) {
+        self.grain_filter_cutoff_spread = octaves;
+        self.store_parameter(ParameterId::GrainFilterCutoffSpread, octaves);
+    }
+
+    /**
+     * Base quality factor of the grain filter.
+     */
+    pub fn set_grain_filter_resonance(&mut self, resonance: f32) {
+        self.grain_filter_resonance = resonance;
+        self.store_parameter(ParameterId::GrainFilterResonance, resonance);
+    }
+
+    /**
+     * 0.0 - 1.0, random resonance of each grain up to 4 times higher or lower.
+     */
+    pub fn set_grain_filter_resonance_spread(&mut self, spread: f32) {
+        self.grain_filter_resonance_spread = spread;
+        self.store_parameter(ParameterId::GrainFilterResonanceSpread, spread);
+    }
+
+    /**
+     * Cutoff change over the lifetime of each grain, in octaves.
+     */
+    pub fn set_grain_filter_sweep(&mut self, octaves: f32) {
+        self.grain_filter_sweep = octaves;
+        self.store_parameter(ParameterId::GrainFilterSweep, octaves);
+    }
+
+    pub fn set_spectral_freeze(&mut self, is_frozen: bool) {
+        self.spectral.set_freeze(is_frozen);
+        self.store_parameter(ParameterId::SpectralFreeze, is_frozen as u8 as f32);
+    }
+
+    pub fn set_spectral_phase_randomization(&mut self, phase_randomization: f32) {
+        self.spectral.set_phase_randomization(phase_randomization);
+        self.store_parameter(ParameterId::SpectralPhaseRandomization, phase_randomization);
+    }
+
+    pub fn set_spectral_quantization(&mut self, quantization: f32) {
+        self.spectral.set_quantization(quantization);
+        self.store_parameter(ParameterId::SpectralQuantization, quantization);
+    }
+
+    /**
+     * Attack time of the input envelope follower, in milliseconds.
+     */
+    pub fn set_envelope_attack(&mut self, milliseconds: f32) {
+        self.envelope_follower.set_attack(milliseconds);
+        self.store_parameter(ParameterId::EnvelopeAttack, milliseconds);
+    }
+
+    /**
+     * Release time of the input envelope follower, in milliseconds.
+     */
+    pub fn set_envelope_release(&mut self, milliseconds: f32) {
+        self.envelope_follower.set_release(milliseconds);
+        self.store_parameter(ParameterId::EnvelopeRelease, milliseconds);
+    }
+
+    /**
+     * Gain of the input envelope follower in dB, so that quiet inputs can
+     * reach a full envelope.
+     */
+    pub fn set_envelope_gain(&mut self, decibels: f32) {
+        self.envelope_follower.set_gain(decibels);
+        self.store_parameter(ParameterId::EnvelopeGain, decibels);
+    }
+
+    /**
+     * -1.0 - 1.0, a positive depth thins the cloud out in silence,
+     * a negative one when the input is loud.
+     */
+    pub fn set_envelope_to_density(&mut self, depth: f32) {
+        self.envelope_to_density = depth;
+        self.store_parameter(ParameterId::EnvelopeToDensity, depth);
+    }
+
+    pub fn set_envelope_to_volume(&mut self, depth: f32) {
+        self.envelope_to_volume = depth;
+        self.store_parameter(ParameterId::EnvelopeToVolume, depth);
+    }
+
+    pub fn set_envelope_to_feedback(&mut self, depth: f32) {
+        self.envelope_to_feedback = depth;
+        self.store_parameter(ParameterId::EnvelopeToFeedback, depth);
+    }
+
+    /**
+     * -1.0 - 1.0, scales `position` of new grains and of spectral frames
+     * towards the write head. The loop mode ignores it.
+     */
+    pub fn set_envelope_to_position(&mut self, depth: f32) {
+        self.envelope_to_position = depth;
+        self.store_parameter(ParameterId::EnvelopeToPosition, depth);
+    }
+
+    /**
+     * Current value of the input envelope follower, 0.0 - 1.0, for metering.
+     */
+    pub fn envelope(&self) -> f32 {
+        self.envelope
+    }
+
+    fn modulated_position(&self) -> f32 {
+        let modulation = follower::modulation(self.envelope_to_position, self.envelope);
+        (self.position * modulation).max(1.0)
+    }
+
+    /**
+     * Morphs between two or four snapshots of options, following the morph
+     * position. `None` keeps the current values.
+     */
+    pub fn set_morph(&mut self, morph: Option<Morph>) {
+        self.morph = morph;
+        self.apply_morph();
+    }
+
+    /**
+     * 0.0 - 1.0 on each axis, `y` is ignored for a crossfade.
+     */
+    pub fn set_morph_position(&mut self, x: f32, y: f32) {
+        self.store_parameter(ParameterId::MorphX, x);
+        self.store_parameter(ParameterId::MorphY, y);
+        self.apply_morph();
+    }
+
+    /**
+     * Sets every parameter that changed to its morphed value.
+     */
+    fn apply_morph(&mut self) {
+        let x = self.get_parameter(ParameterId::MorphX);
+        let y = self.get_parameter(ParameterId::MorphY);
+        for id in ParameterId::ALL.iter() {
+            if *id == ParameterId::MorphX || *id == ParameterId::MorphY {
+                continue;
+            }
+            let plain = match &self.morph {
+                Some(morph) => morph.value(*id, x, y),
+                None => return,
+            };
+            if plain != self.get_parameter(*id) {
+                self.set_parameter(*id, plain);
+            }
+        }
+    }
+
+    /**
+     * Sets any parameter from its plain value, see `ParameterDescriptor`
+     * for ranges and units. Values are clamped to the range.
+     */
+    pub fn set_parameter(&mut self, id: ParameterId, plain: f32) {
+        let descriptor = id.descriptor();
+        let plain = descriptor.clamp(plain);
+        let is_on = plain >= 0.5;
+        let optional = if descriptor.is_off(plain) {
+            None
+        } else {
+            Some(plain)
+        };
+
+        match id {
+            ParameterId::Mode => self.set_mode(Mode::from_index(plain as usize)),
+            ParameterId::Position => self.set_position_milliseconds(plain),
+            ParameterId::Density => self.set_density(plain),
+            ParameterId::Overlap => self.set_overlap(optional),
+            ParameterId::GrainTrigger => {
+                self.set_grain_trigger(GrainTrigger::from_index(plain as usize))
+            }
+            ParameterId::OnsetSensitivity => self.set_onset_sensitivity(plain),
+            ParameterId::OnsetRefractory => self.set_onset_refractory(plain),
+            ParameterId::OnsetBurst => self.set_onset_burst(plain as usize),
+            ParameterId::Tempo => self.set_tempo(plain),
+            ParameterId::PatternDivision => self.set_pattern_division(plain as usize),
+            ParameterId::Duration => self.set_duration_milliseconds(plain),
+            ParameterId::Pitch => self.set_pitch(plain),
+            ParameterId::GrainSpread => self.set_grain_spread(plain),
+            ParameterId::ZeroCrossingWindow => self.set_zero_crossing_window(plain),
+            ParameterId::GrainFilter => {
+                self.set_grain_filter(GrainFilter::from_index(plain as usize))
+            }
+            ParameterId::GrainFilterCutoff => self.set_grain_filter_cutoff(plain),
+            ParameterId::GrainFilterCutoffSpread => self.set_grain_filter_cutoff_spread(plain),
+            ParameterId::GrainFilterResonance => self.set_grain_filter_resonance(plain),
+            ParameterId::GrainFilterResonanceSpread => {
+                self.set_grain_filter_resonance_spread(plain)
+            }
+            ParameterId::GrainFilterSweep => self.set_grain_filter_sweep(plain),
+            ParameterId::Volume => self.set_volume(plain),
+            ParameterId::Feedback => self.set_feedback(plain),
+            ParameterId::FeedbackHighPass => self.set_feedback_high_pass(optional),
+            ParameterId::FeedbackLowPass => self.set_feedback_low_pass(optional),
+            ParameterId::FeedbackDcBlocker => self.set_feedback_dc_blocker(is_on),
+            ParameterId::FeedbackSaturation => self.set_feedback_saturation(optional),
+            ParameterId::WetDry => self.set_wet_dry(plain),
+            ParameterId::CrossfadeLaw => {
+                self.set_crossfade_law(CrossfadeLaw::from_index(plain as usize))
+            }
+            ParameterId::DryLevel => self.set_dry_level(plain),
+            ParameterId::WetLevel => self.set_wet_level(plain),
+            ParameterId::DryLatencyCompensation => self.set_dry_latency_compensation(is_on),
+            ParameterId::Diffusion => self.set_diffusion(plain),
+            ParameterId::ReverbRouting => {
+                self.set_reverb_routing(ReverbRouting::from_index(plain as usize))
+            }
+            ParameterId::ReverbAmount => self.set_reverb_amount(plain),
+            ParameterId::ReverbRoomSize => self.set_reverb_room_size(plain),
+            ParameterId::ReverbDamping => self.set_reverb_damping(plain),
+            ParameterId::ReverbWidth => self.set_reverb_width(plain),
+            ParameterId::ReverbFreeze => self.set_reverb_freeze(is_on),
+            ParameterId::SpectralFreeze => self.set_spectral_freeze(is_on),
+            ParameterId::SpectralPhaseRandomization => self.set_spectral_phase_randomization(plain),
+            ParameterId::SpectralQuantization => self.set_spectral_quantization(plain),
+            ParameterId::EnvelopeAttack => self.set_envelope_attack(plain),
+            ParameterId::EnvelopeRelease => self.set_envelope_release(plain),
+            ParameterId::EnvelopeGain => self.set_envelope_gain(plain),
+            ParameterId::EnvelopeToDensity => self.set_envelope_to_density(plain),
+            ParameterId::EnvelopeToVolume => self.set_envelope_to_volume(plain),
+            ParameterId::EnvelopeToFeedback => self.set_envelope_to_feedback(plain),
+            ParameterId::EnvelopeToPosition => self.set_envelope_to_position(plain),
+            ParameterId::MorphX => {
+                self.set_morph_position(plain, self.get_parameter(ParameterId::MorphY))
+            }
+            ParameterId::MorphY => {
+                self.set_morph_position(self.get_parameter(ParameterId::MorphX), plain)
+            }
+        }
+    }
+
+    /**
+     * Sets any parameter from a 0.0 - 1.0 value, mapped with the parameter taper.
+     */
+    pub fn set_parameter_normalized(&mut self, id: ParameterId, normalized: f32) {
+        self.set_parameter(id, id.descriptor().denormalize(normalized));
+    }
+
+    /**
+     * Plain value of any parameter.
+     */
+    pub fn get_parameter(&self, id: ParameterId) -> f32 {
+        self.parameters[id.index()]
+    }
+
+    pub fn get_parameter_normalized(&self, id: ParameterId) -> f32 {
+        id.descriptor().normalize(self.get_parameter(id))
+    }
+
+    fn store_parameter(&mut self, id: ParameterId, plain: f32) {
+        self.parameters[id.index()] = plain;
+    }
+
+    fn store_optional_parameter(&mut self, id: ParameterId, plain: Option<f32>) {
+        let descriptor = id.descriptor();
+        let plain = plain.unwrap_or_else(|| descriptor.off.unwrap_or(descriptor.default));
+        self.store_parameter(id, plain);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        GrainTrigger, Granulator, GranulatorOptions, Mode, ReverbRouting, MAX_PENDING_GRAINS,
+    };
+    use crate::grain_source::{GrainEnvelope, GrainParameterSource, GrainParameters};
+    use crate::morph::Morph;
+    use crate::parameter::ParameterId;
+    use crate::pattern::Pattern;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn bypassed_reverb_leaves_output_unprocessed() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            wet_dry: 0.0,
+            volume: 1.0,
+            reverb_routing: ReverbRouting::Bypass,
+            ..GranulatorOptions::default()
+        });
+        assert_eq!(granulator.process([0.5, -0.25]), [0.5, -0.25]);
+    }
+
+    #[test]
+    fn dry_latency_compensation_follows_mode() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            mode: Mode::Spectral,
+            wet_dry: 0.0,
+            volume: 1.0,
+            reverb_routing: ReverbRouting::Bypass,
+            dry_latency_compensation: true,
+            ..GranulatorOptions::default()
+        });
+        assert_eq!(granulator.process([1.0, 1.0]), [0.0, 0.0]);
+        for _ in 1..granulator.latency() {
+            granulator.process([0.0, 0.0]);
+        }
+        assert_eq!(granulator.process([0.0, 0.0]), [1.0, 1.0]);
+
+        granulator.set_mode(Mode::Cloud);
+        assert_eq!(granulator.process([1.0, 1.0]), [1.0, 1.0]);
+    }
+
+    #[test]
+    fn options_round_trip() {
+        let options = GranulatorOptions {
+            mode: Mode::Spectral,
+            position: 1234.0,
+            feedback_high_pass: Some(100.0),
+            reverb_routing: ReverbRouting::Grains,
+            spectral_freeze: true,
+            ..GranulatorOptions::default()
+        };
+        let restored = Granulator::<f32, 2>::new(options.clone()).options();
+        assert_eq!(restored.mode, Mode::Spectral);
+        assert_eq!(restored.position, 1234.0);
+        assert_eq!(restored.feedback_high_pass, Some(100.0));
+        assert_eq!(restored.feedback_low_pass, None);
+        assert_eq!(restored.reverb_routing, ReverbRouting::Grains);
+        assert!(restored.spectral_freeze);
+    }
+
+    #[test]
+    fn parameters_default_to_options() {
+        let granulator: Granulator = Granulator::new(GranulatorOptions::default());
+        for id in ParameterId::ALL.iter() {
+            assert_eq!(granulator.get_parameter(*id), id.descriptor().default);
+        }
+    }
+
+    #[test]
+    fn set_parameter_reaches_setters() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions::default());
+        granulator.set_parameter(ParameterId::Mode, 1.2);
+        assert_eq!(granulator.mode, Mode::Loop);
+        assert_eq!(granulator.get_parameter(ParameterId::Mode), 1.0);
+
+        granulator.set_parameter_normalized(ParameterId::Pitch, 0.5);
+        assert!((granulator.pitch - 1.0).abs() < 1e-5);
+
+        // Positions are in milliseconds whatever the sample rate, up to the
+        // whole delay line.
+        granulator.set_parameter(ParameterId::Position, 1e9);
+        assert_eq!(granulator.get_parameter(ParameterId::Position), 10000.0);
+        assert_eq!(granulator.options().position, granulator.max_delay() as f32);
+        let mut granulator: Granulator =
+            Granulator::with_sample_rate(GranulatorOptions::for_sample_rate(96000), 96000);
+        assert_eq!(granulator.position_milliseconds(), 1000.0);
+        granulator.set_parameter_normalized(ParameterId::Position, 1.0);
+        assert_eq!(granulator.options().position, 960000.0);
+        granulator.set_position_fraction(1.0);
+        assert_eq!(
+            granulator.get_parameter_normalized(ParameterId::Position),
+            1.0
+        );
+
+        granulator.set_feedback_low_pass(Some(440.0));
+        assert_eq!(
+            granulator.get_parameter(ParameterId::FeedbackLowPass),
+            440.0
+        );
+        granulator.set_feedback_low_pass(None);
+        assert_eq!(
+            granulator.get_parameter(ParameterId::FeedbackLowPass),
+            40000.0
+        );
+
+        // The ends of the cutoff ranges are not mistaken for the bypass.
+        granulator.set_feedback_high_pass(Some(20.0));
+        assert_eq!(
+            granulator.get_parameter(ParameterId::FeedbackHighPass),
+            20.0
+        );
+        granulator.set_parameter_normalized(ParameterId::FeedbackHighPass, 0.0);
+        assert_eq!(
+            granulator.get_parameter(ParameterId::FeedbackHighPass),
+            10.0
+        );
+        granulator.set_feedback_low_pass(Some(20000.0));
+        assert_eq!(
+            granulator.get_parameter(ParameterId::FeedbackLowPass),
+            20000.0
+        );
+        granulator.set_parameter_normalized(ParameterId::FeedbackLowPass, 1.0);
+        assert_eq!(
+            granulator.get_parameter(ParameterId::FeedbackLowPass),
+            40000.0
+        );
+    }
+
+    #[test]
+    fn morph_position_is_a_parameter() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions::default());
+        granulator.set_morph(Some(Morph::crossfade(
+            &GranulatorOptions {
+                density: 10.0,
+                ..GranulatorOptions::default()
+            },
+            &GranulatorOptions {
+                density: 90.0,
+                mode: Mode::Loop,
+                ..GranulatorOptions::default()
+            },
+            granulator.sample_rate(),
+        )));
+        assert_eq!(granulator.get_parameter(ParameterId::Density), 10.0);
+        assert_eq!(granulator.mode, Mode::Cloud);
+
+        granulator.set_parameter_normalized(ParameterId::MorphX, 1.0);
+        assert!((granulator.get_parameter(ParameterId::Density) - 90.0).abs() < 1e-3);
+        assert_eq!(granulator.mode, Mode::Loop);
+        assert_eq!(granulator.options().morph_x, 1.0);
+    }
+
+    #[test]
+    fn morph_keeps_positions_at_any_sample_rate() {
+        let options = GranulatorOptions::for_sample_rate(96000);
+        let mut granulator: Granulator = Granulator::with_sample_rate(options.clone(), 96000);
+        let far = GranulatorOptions {
+            position: 900000.0,
+            ..options.clone()
+        };
+        granulator.set_morph(Some(Morph::crossfade(&options, &far, 96000)));
+        granulator.set_morph_position(1.0, 0.0);
+        assert!((granulator.options().position - 900000.0).abs() < 1.0);
+        assert!((granulator.position_milliseconds() - 9375.0).abs() < 0.01);
+    }
+
+    fn render(granulator: &mut Granulator, num_samples: usize) -> Vec<[f32; 2]> {
+        (0..num_samples)
+            .map(|i| {
+                let sample = (i as f32 * 0.01).sin();
+                granulator.process([sample, sample])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn restore_resumes_exactly() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            diffusion: 0.5,
+            ..GranulatorOptions::default()
+        });
+        render(&mut granulator, 10000);
+        let state = granulator.snapshot();
+        let expected = render(&mut granulator, 20000);
+
+        granulator.set_pitch(2.0);
+        granulator.restore(&state);
+        assert_eq!(render(&mut granulator, 20000), expected);
+    }
+
+    #[test]
+    fn reset_silences_engine() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            diffusion: 0.5,
+            ..GranulatorOptions::default()
+        });
+        render(&mut granulator, 10000);
+        granulator.reset();
+        for _ in 0..20000 {
+            assert_eq!(granulator.process([0.0, 0.0]), [0.0, 0.0]);
+        }
+        assert_eq!(granulator.get_parameter(ParameterId::Diffusion), 0.5);
+    }
+
+    #[test]
+    fn spread_places_grains_across_channels() {
+        let options = GranulatorOptions {
+            position: 100.0,
+            feedback: 0.0,
+            reverb_routing: ReverbRouting::Bypass,
+            ..GranulatorOptions::default()
+        };
+        let channel_energy = |grain_spread: f32| {
+            let mut granulator: Granulator<f32, 4> = Granulator::new(GranulatorOptions {
+                grain_spread,
+                ..options.clone()
+            });
+            granulator.set_seed(1);
+            let mut energy = [0.0; 4];
+            for i in 0..20000 {
+                let frame = granulator.process([(i as f32 * 0.01).sin(), 0.0, 0.0, 0.0]);
+                for (energy, sample) in energy.iter_mut().zip(frame.iter()) {
+                    *energy += sample * sample;
+                }
+            }
+            energy
+        };
+
+        let [first, others @ ..] = channel_energy(0.0);
+        assert!(first > 0.0);
+        assert!(others.iter().all(|energy| *energy == 0.0));
+        assert!(channel_energy(1.0).iter().all(|energy| *energy > 0.0));
+    }
+
+    #[test]
+    fn processes_mono() {
+        let mut granulator: Granulator<f32, 1> = Granulator::new(GranulatorOptions {
+            position: 100.0,
+            ..GranulatorOptions::default()
+        });
+        let energy: f32 = (0..20000)
+            .map(|i| granulator.process([(i as f32 * 0.01).sin()])[0].powi(2))
+            .sum();
+        assert!(energy > 0.0);
+    }
+
+    #[test]
+    fn max_grains_limits_polyphony() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            density: 100.0,
+            duration: 10000.0,
+            ..GranulatorOptions::default()
+        });
+        granulator.set_max_grains(1000);
+        let mut max_active_grains = 0;
+        for _ in 0..10000 {
+            granulator.process([0.0, 0.0]);
+            max_active_grains = max_active_grains.max(granulator.num_active_grains());
+        }
+        assert!(max_active_grains > 100);
+
+        granulator.set_max_grains(3);
+        for _ in 0..1000 {
+            granulator.process([0.0, 0.0]);
+            assert!(granulator.num_active_grains() <= 3);
+        }
+        granulator.set_max_grains(0);
+        assert_eq!(granulator.max_grains(), 1);
+    }
+
+    #[test]
+    fn overlap_follows_grain_length() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            overlap: Some(4.0),
+            ..GranulatorOptions::default()
+        });
+        granulator.set_seed(1);
+        let mut mean_active_grains = |duration: f32, pitch: f32| {
+            granulator.set_duration(duration);
+            granulator.set_pitch(pitch);
+            let num_samples = 100000;
+            let sum: usize = (0..num_samples)
+                .map(|_| {
+                    granulator.process([0.0, 0.0]);
+                    granulator.num_active_grains()
+                })
+                .sum();
+            sum as f32 / num_samples as f32
+        };
+        // Lets the cloud build up.
+        mean_active_grains(1000.0, 1.0);
+        for (duration, pitch) in [(1000.0, 1.0), (4000.0, 1.0), (4000.0, 2.0)] {
+            let mean = mean_active_grains(duration, pitch);
+            assert!((mean - 4.0).abs() < 0.5, "{} grains", mean);
+        }
+    }
+
+    #[test]
+    fn envelope_thins_cloud_out_in_silence() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            density: 50.0,
+            envelope_to_density: 1.0,
+            ..GranulatorOptions::default()
+        });
+        for _ in 0..100000 {
+            granulator.process([0.0, 0.0]);
+        }
+        assert_eq!(granulator.envelope(), 0.0);
+        assert_eq!(granulator.num_active_grains(), 0);
+
+        for _ in 0..10000 {
+            granulator.process([1.0, 1.0]);
+        }
+        assert!(granulator.envelope() > 0.99);
+        assert!(granulator.num_active_grains() > 0);
+    }
+
+    #[test]
+    fn triggers_grains_at_offset() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            grain_trigger: GrainTrigger::Onsets,
+            ..GranulatorOptions::default()
+        });
+        let parameters = GrainParameters {
+            position: 100.0,
+            duration: 10.0,
+            pitch: 1.0,
+            pan: 0.0,
+            amplitude: 1.0,
+            envelope: GrainEnvelope::Parabolic,
+        };
+        assert!(granulator.trigger_grain(parameters, 3));
+        assert!(granulator.trigger_grain(parameters, 5));
+        let num_active_grains: Vec<usize> = (0..20)
+            .map(|_| {
+                granulator.process([0.0, 0.0]);
+                granulator.num_active_grains()
+            })
+            .collect();
+        // Grains count as active until the end of their last sample.
+        assert_eq!(
+            num_active_grains,
+            vec![0, 0, 0, 1, 1, 2, 2, 2, 2, 2, 2, 2, 1, 1, 0, 0, 0, 0, 0, 0]
+        );
+
+        for _ in 0..MAX_PENDING_GRAINS {
+            assert!(granulator.trigger_grain(parameters, 100));
+        }
+        assert!(!granulator.trigger_grain(parameters, 100));
+
+        // Grains waiting in cloud mode don't pile up in other modes.
+        granulator.set_mode(Mode::Loop);
+        assert!(!granulator.trigger_grain(parameters, 0));
+        granulator.set_mode(Mode::Cloud);
+        for _ in 0..200 {
+            granulator.process([0.0, 0.0]);
+            assert_eq!(granulator.num_active_grains(), 0);
+        }
+        assert!(granulator.trigger_grain(parameters, 0));
+    }
+
+    #[test]
+    fn refuses_grains_that_never_end() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            grain_trigger: GrainTrigger::Onsets,
+            ..GranulatorOptions::default()
+        });
+        let parameters = GrainParameters {
+            position: 100.0,
+            duration: 10.0,
+            pitch: 0.0,
+            pan: 0.0,
+            amplitude: 1.0,
+            envelope: GrainEnvelope::Parabolic,
+        };
+        assert!(!granulator.trigger_grain(parameters, 0));
+        assert!(!granulator.trigger_grain(
+            GrainParameters {
+                pitch: -1.0,
+                ..parameters
+            },
+            0
+        ));
+        assert!(!granulator.trigger_grain(
+            GrainParameters {
+                pitch: 1.0,
+                position: f32::NAN,
+                ..parameters
+            },
+            0
+        ));
+        assert!(!granulator.trigger_grain(
+            GrainParameters {
+                pitch: 1.0,
+                duration: f32::INFINITY,
+                ..parameters
+            },
+            0
+        ));
+        assert!(granulator.trigger_grain(
+            GrainParameters {
+                pitch: 1.0,
+                ..parameters
+            },
+            0
+        ));
+        for _ in 0..20 {
+            granulator.process([0.0, 0.0]);
+        }
+        assert_eq!(granulator.num_active_grains(), 0);
+    }
+
+    #[test]
+    fn starts_grains_at_zero_crossings() {
+        // First sample of a grain without attack reading a 441 Hz sine.
+        let first_sample = |zero_crossing_window| {
+            let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+                grain_trigger: GrainTrigger::Pattern,
+                wet_dry: 1.0,
+                reverb_routing: ReverbRouting::Bypass,
+                zero_crossing_window,
+                ..GranulatorOptions::default()
+            });
+            granulator.set_pattern(Pattern::from_probabilities(&[0.0]));
+            let sine = |i: usize| {
+                let sample = (2.0 * std::f32::consts::PI * i as f32 / 100.0).sin();
+                [sample, sample]
+            };
+            for i in 0..2000 {
+                granulator.process(sine(i));
+            }
+            let parameters = GrainParameters {
+                position: 1025.0,
+                duration: 1000.0,
+                pitch: 1.0,
+                pan: 0.0,
+                amplitude: 1.0,
+                envelope: GrainEnvelope::Trapezoid {
+                    attack: 0.0,
+                    release: 0.0,
+                },
+            };
+            granulator.trigger_grain(parameters, 0);
+            granulator.process(sine(2000))[0]
+        };
+        assert!(first_sample(0.0).abs() > 0.1);
+        assert!(first_sample(2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn pattern_plays_on_tempo() {
+        let mut granulator: Granulator = Granulator::with_sample_rate(
+            GranulatorOptions {
+                grain_trigger: GrainTrigger::Pattern,
+                duration: 100.0,
+                tempo: 120.0,
+                pattern_division: 4,
+                ..GranulatorOptions::default()
+            },
+            48000,
+        );
+        let mut pattern = Pattern::euclidean(2, 4, 0);
+        pattern.steps_mut()[2].pitch = 12.0;
+        granulator.set_pattern(pattern);
+        // One step every 6000 samples, the second hit an octave up plays
+        // its 100 samples in 50.
+        let num_active_grains: Vec<usize> = (0..24000)
+            .map(|_| {
+                granulator.process([0.0, 0.0]);
+                granulator.num_active_grains()
+            })
+            .collect();
+        for (sample, expected) in [(0, 1), (98, 1), (99, 0), (6000, 0), (12000, 1), (12049, 0)] {
+            assert_eq!(num_active_grains[sample], expected, "sample {}", sample);
+        }
+    }
+
+    struct Silent;
+
+    impl GrainParameterSource for Silent {
+        fn next_grain(&mut self, defaults: GrainParameters) -> GrainParameters {
+            GrainParameters {
+                amplitude: 0.0,
+                ..defaults
+            }
+        }
+    }
+
+    struct Stalled;
+
+    impl GrainParameterSource for Stalled {
+        fn next_grain(&mut self, defaults: GrainParameters) -> GrainParameters {
+            GrainParameters {
+                pitch: 0.0,
+                ..defaults
+            }
+        }
+    }
+
+    #[test]
+    fn grain_parameter_source_cannot_stall_grains() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            duration: 100.0,
+            ..GranulatorOptions::default()
+        });
+        granulator.set_grain_parameter_source(Some(Box::new(Stalled)));
+        let mut max_grains = 0;
+        for _ in 0..20000 {
+            granulator.process([0.0, 0.0]);
+            max_grains = max_grains.max(granulator.num_active_grains());
+        }
+        assert!(max_grains > 0);
+        // Grains of 100 samples at the default pitch never pile up.
+        assert!(max_grains < granulator.max_grains());
+    }
+
+    #[test]
+    fn grain_parameter_source_decides_grains() {
+        let options = GranulatorOptions {
+            position: 1000.0,
+            wet_dry: 1.0,
+            dry_level: 0.0,
+            reverb_routing: ReverbRouting::Bypass,
+            ..GranulatorOptions::default()
+        };
+        let render = |source: Option<Box<dyn GrainParameterSource>>| {
+            let mut granulator: Granulator = Granulator::new(options.clone());
+            granulator.set_grain_parameter_source(source);
+            (0..20000)
+                .map(|_| granulator.process([0.5, 0.5])[0].abs())
+                .sum::<f32>()
+        };
+        assert!(render(None) > 0.0);
+        assert_eq!(render(Some(Box::new(Silent))), 0.0);
+    }
+
+    #[test]
+    fn grains_read_sidechain() {
+        let options = GranulatorOptions {
+            position: 1000.0,
+            wet_dry: 1.0,
+            ..GranulatorOptions::default()
+        };
+        let mut granulator: Granulator = Granulator::new(options.clone());
+        let output: f32 = (0..20000)
+            .map(|_| granulator.process_with_sidechain([0.0, 0.0], [0.5, 0.5])[0].abs())
+            .sum();
+        assert!(output > 0.0);
+
+        let mut granulator: Granulator = Granulator::new(options);
+        let output: f32 = (0..20000)
+            .map(|_| granulator.process_with_sidechain([0.5, 0.5], [0.0, 0.0])[0].abs())
+            .sum();
+        assert_eq!(output, 0.0);
+    }
+
+    #[test]
+    fn onsets_start_grain_bursts() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            grain_trigger: GrainTrigger::Onsets,
+            onset_burst: 4,
+            duration: 4000.0,
+            ..GranulatorOptions::default()
+        });
+        for _ in 0..10000 {
+            granulator.process([0.0, 0.0]);
+        }
+        assert_eq!(granulator.num_active_grains(), 0);
+
+        granulator.process([0.8, 0.8]);
+        for _ in 0..3500 {
+            granulator.process([0.0, 0.0]);
+        }
+        assert_eq!(granulator.num_active_grains(), 4);
+    }
+
+    static NUM_STARTED_GRAINS: AtomicUsize = AtomicUsize::new(0);
+
+    #[test]
+    fn bursts_add_to_scheduled_grains() {
+        let mut granulator: Granulator = Granulator::new(GranulatorOptions {
+            grain_trigger: GrainTrigger::SchedulerAndOnsets,
+            onset_burst: 4,
+            // Interonsets below a sample, so that the scheduler starts
+            // grains at its highest, steady rate.
+            density: 1_000_000.0,
+            duration: 100.0,
+            new_grain_hook: Some(|_| {
+                NUM_STARTED_GRAINS.fetch_add(1, Ordering::Relaxed);
+            }),
+            ..GranulatorOptions::default()
+        });
+        for _ in 0..10000 {
+            granulator.process([0.0, 0.0]);
+        }
+        NUM_STARTED_GRAINS.store(0, Ordering::Relaxed);
+        for _ in 0..100 {
+            granulator.process([0.0, 0.0]);
+        }
+        let num_scheduled_grains = NUM_STARTED_GRAINS.swap(0, Ordering::Relaxed);
+
+        granulator.process([0.8, 0.8]);
+        for _ in 0..99 {
+            granulator.process([0.0, 0.0]);
+        }
+        assert_eq!(
+            NUM_STARTED_GRAINS.load(Ordering::Relaxed),
+            num_scheduled_grains + 4
+        );
+    }
+
+    #[test]
+    fn unit_setters_use_sample_rate() {
+        let mut granulator: Granulator =
+            Granulator::with_sample_rate(GranulatorOptions::default(), 48000);
+        granulator.set_position_milliseconds(500.0);
+        assert_eq!(granulator.options().position, 24000.0);
+        granulator.set_position_seconds(2.0);
+        assert_eq!(granulator.position_milliseconds(), 2000.0);
+        granulator.set_position_fraction(0.5);
+        assert_eq!(granulator.position_milliseconds(), 5000.0);
+        granulator.set_duration_milliseconds(20.0);
+        assert_eq!(granulator.options().duration, 960.0);
+
+        granulator.set_pitch_semitones(-12.0, 0.0);
+        assert!((granulator.get_parameter(ParameterId::Pitch) - 0.5).abs() < 1e-6);
+        granulator.set_pitch_semitones(7.0, 50.0);
+        assert!((granulator.pitch_semitones() - 7.5).abs() < 1e-4);
+        granulator.set_pitch_volts(1.0);
+        assert!((granulator.get_parameter(ParameterId::Pitch) - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn double_precision_matches_single_precision() {
+        let options = GranulatorOptions {
+            position: 100.0,
+            feedback: 0.0,
+            diffusion: 0.5,
+            ..GranulatorOptions::default()
+        };
+        let mut single: Granulator = Granulator::new(options.clone());
+        let mut double: Granulator<f64> = Granulator::new(options);
+        single.set_seed(1);
+        double.set_seed(1);
+        for i in 0..20000 {
+            let sample = (i as f32 * 0.01).sin();
+            let [left, right] = single.process([sample, sample]);
+            let [double_left, double_right] = double.process([sample as f64, sample as f64]);
+            assert!((left as f64 - double_left).abs() < 1e-3);
+            assert!((right as f64 - double_right).abs() < 1e-3);
+        }
     }
 }
@@ -1,9 +1,48 @@
 mod delay_line;
+mod diffuser;
+mod feedback;
+mod fft;
+mod filter;
+mod follower;
 mod frame;
 mod grain;
+mod grain_pool;
+mod grain_source;
 mod granulator;
+mod looper;
+mod mixer;
+mod morph;
+mod onset;
+#[cfg(test)]
 mod parabolic_envelope;
+mod parameter;
+mod pattern;
+#[cfg(feature = "serde")]
+mod preset;
+mod reverb;
+mod sample;
 mod scheduler;
+mod spectral;
+mod units;
 
+pub use grain_source::{GrainEnvelope, GrainParameterSource, GrainParameters};
+pub use granulator::GrainFilter;
+pub use granulator::GrainTrigger;
 pub use granulator::Granulator;
 pub use granulator::GranulatorOptions;
+pub use granulator::GranulatorState;
+pub use granulator::Mode;
+pub use granulator::ReverbRouting;
+pub use granulator::MAX_PENDING_GRAINS;
+pub use granulator::MAX_POLYPHONY;
+pub use mixer::CrossfadeLaw;
+pub use morph::Morph;
+pub use parameter::{ParameterDescriptor, ParameterId, Taper, Unit, NUM_PARAMETERS};
+pub use pattern::{Pattern, PatternStep, MAX_PATTERN_STEPS};
+#[cfg(feature = "serde")]
+pub use preset::{Preset, PresetError, PRESET_VERSION};
+pub use sample::Sample;
+pub use units::{
+    milliseconds_to_samples, ratio_to_semitones, samples_to_milliseconds, semitones_to_ratio,
+    volts_to_ratio,
+};
@@ -0,0 +1,162 @@
+use crate::delay_line::DelayLine;
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+// Length of the crossfade between two loop cycles, in samples.
+const CROSSFADE_SAMPLES: f32 = 1000.0;
+
+#[derive(Copy, Clone)]
+struct LoopHead<T> {
+    is_active: bool,
+    // Position to read from delay line.
+    position: T,
+    num_samples_played: T,
+}
+
+impl<T: Sample> LoopHead<T> {
+    fn new() -> LoopHead<T> {
+        LoopHead {
+            is_active: false,
+            position: T::zero(),
+            num_samples_played: T::zero(),
+        }
+    }
+
+    fn start(&mut self, position: T) {
+        self.position = position;
+        self.num_samples_played = T::zero();
+        self.is_active = true;
+    }
+
+    /**
+     * Linear fade in over the first `crossfade` samples of the loop, and
+     * fade out over the `crossfade` samples following it, while the next
+     * head fades in.
+     */
+    fn gain(&self, duration: T, crossfade: T) -> T {
+        let fade_in = self.num_samples_played / crossfade;
+        let fade_out = (duration + crossfade - self.num_samples_played) / crossfade;
+        fade_in.min(fade_out).max(T::zero()).min(T::one())
+    }
+}
+
+/**
+ * Replays a loop of `duration` samples taken `position` samples behind
+ * the write head of the delay line. Two read heads take turns: a new one
+ * starts every `duration` samples while the previous one plays on past
+ * the end of the loop, crossfading into the beginning of the next cycle.
+ *
+ * The loop stays anchored to the part of the delay line it was taken from
+ * until `position` or `duration` change, or until it is about to be
+ * overwritten by the write head, in which case a new loop is taken.
+ */
+#[derive(Clone)]
+pub struct Looper<T = f32> {
+    heads: [LoopHead<T>; 2],
+    current: usize,
+    // Number of samples since the loop was taken.
+    age: T,
+    loop_position: T,
+    loop_duration: T,
+}
+
+impl<T: Sample> Looper<T> {
+    pub fn new() -> Looper<T> {
+        Looper {
+            heads: [LoopHead::new(); 2],
+            current: 0,
+            age: T::zero(),
+            loop_position: T::zero(),
+            loop_duration: T::zero(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Looper::new();
+    }
+
+    pub fn process<const N: usize>(
+        &mut self,
+        delay_line: &DelayLine<T, N>,
+        position: T,
+        duration: T,
+        pitch: T,
+    ) -> Frame<T, N> {
+        let crossfade = T::from_f32(CROSSFADE_SAMPLES)
+            .min(duration / T::from_f32(2.0))
+            .max(T::one());
+
+        let is_overwritten = position + duration + self.age >= delay_line.max_length - T::one();
+        if position != self.loop_position || duration != self.loop_duration || is_overwritten {
+            self.take_loop(position, duration);
+        }
+
+        // The loop starts `duration` samples before `position`, so that it
+        // ends exactly `position` samples behind the write head when taken.
+        let loop_start = position + duration + self.age;
+
+        if !self.heads[self.current].is_active {
+            self.heads[self.current].start(loop_start);
+        }
+        if self.heads[self.current].num_samples_played >= duration {
+            let next = 1 - self.current;
+            self.heads[next].start(loop_start);
+            self.current = next;
+        }
+
+        let mut frame = [T::zero(); N];
+        for head in self.heads.iter_mut() {
+            if !head.is_active {
+                continue;
+            }
+            let gain = head.gain(duration, crossfade);
+            let head_frame = delay_line.read(head.position);
+            for (sample, head_sample) in frame.iter_mut().zip(head_frame.iter()) {
+                *sample += *head_sample * gain;
+            }
+
+            head.num_samples_played += pitch;
+            head.position += T::one() - pitch;
+
+            if head.num_samples_played >= duration + crossfade {
+                head.is_active = false;
+            }
+        }
+        self.age += T::one();
+
+        frame
+    }
+
+    fn take_loop(&mut self, position: T, duration: T) {
+        self.age = T::zero();
+        self.loop_position = position;
+        self.loop_duration = duration;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::delay_line::DelayLine;
+
+    #[test]
+    fn replays_loop_continuously() {
+        let mut delay_line: DelayLine = DelayLine::new(100);
+        let mut looper = super::Looper::new();
+        for i in 0..20 {
+            delay_line.write_and_advance([i as f32, -(i as f32)]);
+        }
+
+        // A loop of 10 samples repeats every 10 samples, each cycle
+        // crossfading over 5 samples into the next one.
+        let mut output = vec![];
+        for _ in 0..40 {
+            output.push(looper.process(&delay_line, 0.0, 10.0, 1.0));
+            delay_line.write_and_advance([0.0, 0.0]);
+        }
+        assert_ne!(output[7], [0.0, 0.0]);
+        assert_ne!(output[7], output[12]);
+        for i in 10..30 {
+            assert_eq!(output[i], output[i + 10]);
+        }
+    }
+}
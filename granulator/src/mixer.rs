@@ -0,0 +1,211 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+/**
+ * How dry and wet gains follow the wet/dry control.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CrossfadeLaw {
+    // Gains sum up to 1.0, uncorrelated signals dip by 3 dB at the midpoint.
+    Linear,
+    // Powers sum up to 1.0, constant loudness for uncorrelated signals.
+    EqualPower,
+}
+
+impl CrossfadeLaw {
+    pub(crate) fn from_index(index: usize) -> CrossfadeLaw {
+        match index {
+            0 => CrossfadeLaw::Linear,
+            _ => CrossfadeLaw::EqualPower,
+        }
+    }
+}
+
+/**
+ * Wet/dry stage. The dry signal keeps its polarity and can be delayed to
+ * line up with the processing latency of the wet signal.
+ */
+pub struct Mixer<T = f32, const N: usize = 2> {
+    law: CrossfadeLaw,
+    wet_dry: f32,
+    dry_level: f32,
+    wet_level: f32,
+    dry_gain: T,
+    wet_gain: T,
+    dry_delay_buffer: Vec<Frame<T, N>>,
+    dry_delay: usize,
+    dry_delay_index: usize,
+}
+
+// `clone_from` reuses the dry delay buffer, so that restoring a state doesn't allocate.
+impl<T: Sample, const N: usize> Clone for Mixer<T, N> {
+    fn clone(&self) -> Self {
+        Mixer {
+            dry_delay_buffer: self.dry_delay_buffer.clone(),
+            ..*self
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.dry_delay_buffer.clone_from(&source.dry_delay_buffer);
+        self.law = source.law;
+        self.wet_dry = source.wet_dry;
+        self.dry_level = source.dry_level;
+        self.wet_level = source.wet_level;
+        self.dry_gain = source.dry_gain;
+        self.wet_gain = source.wet_gain;
+        self.dry_delay = source.dry_delay;
+        self.dry_delay_index = source.dry_delay_index;
+    }
+}
+
+impl<T: Sample, const N: usize> Mixer<T, N> {
+    pub fn new(max_dry_delay: usize) -> Mixer<T, N> {
+        let mut mixer = Mixer {
+            law: CrossfadeLaw::Linear,
+            wet_dry: 1.0,
+            dry_level: 1.0,
+            wet_level: 1.0,
+            dry_gain: T::zero(),
+            wet_gain: T::one(),
+            dry_delay_buffer: vec![[T::zero(); N]; max_dry_delay + 1],
+            dry_delay: 0,
+            dry_delay_index: 0,
+        };
+        mixer.update_gains();
+        mixer
+    }
+
+    pub fn process(&mut self, dry: Frame<T, N>, wet: Frame<T, N>) -> Frame<T, N> {
+        let mut frame = self.delay_dry(dry);
+        for (sample, wet) in frame.iter_mut().zip(wet.iter()) {
+            *sample = *sample * self.dry_gain + *wet * self.wet_gain;
+        }
+        frame
+    }
+
+    fn delay_dry(&mut self, dry: Frame<T, N>) -> Frame<T, N> {
+        if self.dry_delay == 0 {
+            return dry;
+        }
+        let length = self.dry_delay_buffer.len();
+        self.dry_delay_buffer[self.dry_delay_index] = dry;
+        let delayed_index = (self.dry_delay_index + length - self.dry_delay) % length;
+        self.dry_delay_index = (self.dry_delay_index + 1) % length;
+
+        self.dry_delay_buffer[delayed_index]
+    }
+
+    pub fn reset(&mut self) {
+        for frame in self.dry_delay_buffer.iter_mut() {
+            *frame = [T::zero(); N];
+        }
+        self.dry_delay_index = 0;
+    }
+
+    fn update_gains(&mut self) {
+        let wet_dry = self.wet_dry.clamp(0.0, 1.0);
+        let (dry, wet) = match self.law {
+            CrossfadeLaw::Linear => (1.0 - wet_dry, wet_dry),
+            // cos(PI / 2) is not quite 0.0 and would leak some dry signal.
+            CrossfadeLaw::EqualPower if wet_dry == 1.0 => (0.0, 1.0),
+            CrossfadeLaw::EqualPower => ((wet_dry * FRAC_PI_2).cos(), (wet_dry * FRAC_PI_2).sin()),
+        };
+        self.dry_gain = T::from_f32(dry * self.dry_level);
+        self.wet_gain = T::from_f32(wet * self.wet_level);
+    }
+
+    pub fn set_law(&mut self, law: CrossfadeLaw) {
+        self.law = law;
+        self.update_gains();
+    }
+
+    /**
+     * 0.0 - only dry, 1.0 - only wet.
+     */
+    pub fn set_wet_dry(&mut self, wet_dry: f32) {
+        self.wet_dry = wet_dry;
+        self.update_gains();
+    }
+
+    pub fn set_dry_level(&mut self, dry_level: f32) {
+        self.dry_level = dry_level;
+        self.update_gains();
+    }
+
+    pub fn set_wet_level(&mut self, wet_level: f32) {
+        self.wet_level = wet_level;
+        self.update_gains();
+    }
+
+    /**
+     * Delay of the dry signal in samples, up to the maximum given on creation.
+     */
+    pub fn set_dry_delay(&mut self, dry_delay: usize) {
+        let dry_delay = dry_delay.min(self.dry_delay_buffer.len() - 1);
+        if dry_delay != self.dry_delay {
+            self.dry_delay = dry_delay;
+            for frame in self.dry_delay_buffer.iter_mut() {
+                *frame = [T::zero(); N];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrossfadeLaw, Mixer};
+
+    #[test]
+    fn keeps_dry_polarity() {
+        let mut mixer: Mixer = Mixer::new(0);
+        mixer.set_wet_dry(0.0);
+        assert_eq!(mixer.process([0.5, -0.5], [1.0, 1.0]), [0.5, -0.5]);
+    }
+
+    #[test]
+    fn equal_power_keeps_power_at_midpoint() {
+        let mut mixer: Mixer = Mixer::new(0);
+        mixer.set_law(CrossfadeLaw::EqualPower);
+        mixer.set_wet_dry(0.5);
+        let [left, _] = mixer.process([1.0, 0.0], [0.0, 0.0]);
+        let [_, right] = mixer.process([0.0, 0.0], [0.0, 1.0]);
+        assert!((left * left + right * right - 1.0).abs() < 1e-6);
+
+        mixer.set_law(CrossfadeLaw::Linear);
+        assert_eq!(mixer.process([1.0, 0.0], [0.0, 1.0]), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn equal_power_ends_are_exact() {
+        let mut mixer: Mixer = Mixer::new(0);
+        mixer.set_law(CrossfadeLaw::EqualPower);
+        mixer.set_wet_dry(0.0);
+        assert_eq!(mixer.process([1.0, 1.0], [0.5, 0.5]), [1.0, 1.0]);
+        mixer.set_wet_dry(1.0);
+        assert_eq!(mixer.process([1.0, 1.0], [0.5, 0.5]), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn independent_levels() {
+        let mut mixer: Mixer = Mixer::new(0);
+        mixer.set_law(CrossfadeLaw::Linear);
+        mixer.set_wet_dry(0.5);
+        mixer.set_dry_level(2.0);
+        mixer.set_wet_level(0.0);
+        assert_eq!(mixer.process([1.0, 1.0], [1.0, 1.0]), [1.0, 1.0]);
+    }
+
+    #[test]
+    fn delays_dry_signal() {
+        let mut mixer: Mixer = Mixer::new(4);
+        mixer.set_wet_dry(0.0);
+        mixer.set_dry_delay(2);
+        assert_eq!(mixer.process([1.0, 1.0], [0.0, 0.0]), [0.0, 0.0]);
+        assert_eq!(mixer.process([0.0, 0.0], [0.0, 0.0]), [0.0, 0.0]);
+        assert_eq!(mixer.process([0.0, 0.0], [0.0, 0.0]), [1.0, 1.0]);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::granulator::GranulatorOptions;
+use crate::parameter::{ParameterId, Taper, NUM_PARAMETERS};
+
+// Morph position at which discrete parameters switch to the next snapshot.
+const SWITCH_THRESHOLD: f32 = 0.5;
+
+type Snapshot = [f32; NUM_PARAMETERS];
+
+/**
+ * Interpolation between two or four sets of options. Continuous parameters
+ * are interpolated on their taper, so that the middle between 10 and 1000 Hz
+ * is 100 Hz. Discrete ones switch to the closest snapshot at the middle.
+ * The morph position itself is set with `ParameterId::MorphX` and `MorphY`.
+ */
+#[derive(Clone)]
+pub struct Morph {
+    // Corners in order (0, 0), (1, 0), (0, 1), (1, 1).
+    corners: [Snapshot; 4],
+}
+
+impl Morph {
+    /**
+     * From `from` at x = 0.0 to `to` at x = 1.0, whatever the y.
+     * Positions and durations in the options are in samples at `sample_rate`.
+     */
+    pub fn crossfade(
+        from: &GranulatorOptions,
+        to: &GranulatorOptions,
+        sample_rate: usize,
+    ) -> Morph {
+        Morph::xy([from, to, from, to], sample_rate)
+    }
+
+    /**
+     * Corners in order (0, 0), (1, 0), (0, 1), (1, 1).
+     * Positions and durations in the options are in samples at `sample_rate`.
+     */
+    pub fn xy(corners: [&GranulatorOptions; 4], sample_rate: usize) -> Morph {
+        Morph {
+            corners: corners.map(|corner| corner.parameter_values(sample_rate)),
+        }
+    }
+
+    /**
+     * Plain value of a parameter at the given position.
+     */
+    pub fn value(&self, id: ParameterId, x: f32, y: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        let y = y.clamp(0.0, 1.0);
+        let descriptor = id.descriptor();
+        let index = id.index();
+
+        if descriptor.taper == Taper::Stepped {
+            let right = (x >= SWITCH_THRESHOLD) as usize;
+            let top = (y >= SWITCH_THRESHOLD) as usize;
+            return self.corners[right + 2 * top][index];
+        }
+
+        let [bottom_left, bottom_right, top_left, top_right] = self
+            .corners
+            .map(|corner| descriptor.normalize(corner[index]));
+        let bottom = bottom_left * (1.0 - x) + bottom_right * x;
+        let top = top_left * (1.0 - x) + top_right * x;
+        descriptor.denormalize(bottom * (1.0 - y) + top * y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Morph;
+    use crate::granulator::{GranulatorOptions, Mode};
+    use crate::parameter::ParameterId;
+
+    #[test]
+    fn crossfade_follows_taper() {
+        let morph = Morph::crossfade(
+            &GranulatorOptions {
+                pitch: 0.5,
+                mode: Mode::Cloud,
+                ..GranulatorOptions::default()
+            },
+            &GranulatorOptions {
+                pitch: 2.0,
+                mode: Mode::Loop,
+                ..GranulatorOptions::default()
+            },
+            44100,
+        );
+        assert!((morph.value(ParameterId::Pitch, 0.0, 0.0) - 0.5).abs() < 1e-5);
+        assert!((morph.value(ParameterId::Pitch, 0.5, 0.0) - 1.0).abs() < 1e-5);
+        assert!((morph.value(ParameterId::Pitch, 1.0, 0.0) - 2.0).abs() < 1e-5);
+        assert_eq!(morph.value(ParameterId::Mode, 0.49, 0.0), 0.0);
+        assert_eq!(morph.value(ParameterId::Mode, 0.5, 0.0), 1.0);
+    }
+
+    #[test]
+    fn xy_interpolates_between_corners() {
+        let corner = |volume: f32, mode: Mode| GranulatorOptions {
+            volume,
+            mode,
+            ..GranulatorOptions::default()
+        };
+        let morph = Morph::xy(
+            [
+                &corner(0.0, Mode::Cloud),
+                &corner(0.0, Mode::Loop),
+                &corner(0.0, Mode::Spectral),
+                &corner(1.0, Mode::Spectral),
+            ],
+            44100,
+        );
+        assert_eq!(morph.value(ParameterId::Volume, 1.0, 0.0), 0.0);
+        assert_eq!(morph.value(ParameterId::Volume, 1.0, 1.0), 1.0);
+        let centre = ParameterId::Volume.descriptor().denormalize(0.25);
+        assert!((morph.value(ParameterId::Volume, 0.5, 0.5) - centre).abs() < 1e-5);
+        assert_eq!(morph.value(ParameterId::Mode, 0.9, 0.1), 1.0);
+        assert_eq!(morph.value(ParameterId::Mode, 0.1, 0.9), 2.0);
+    }
+}
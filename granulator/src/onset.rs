@@ -0,0 +1,187 @@
+use crate::filter::one_pole_coefficient;
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+// Time constants of the envelopes compared by the detector, in seconds.
+const FAST_ATTACK: f32 = 0.001;
+const FAST_RELEASE: f32 = 0.02;
+const SLOW_TIME: f32 = 0.2;
+// Level below which nothing is detected, -60 dBFS.
+const NOISE_FLOOR: f32 = 0.001;
+
+/**
+ * Detects transients as a fast envelope of the input rising above a slow one.
+ * `sensitivity` 0.0 - 1.0 sets the rise needed, from 24 dB down to 6 dB.
+ * After a detection nothing is detected for the refractory time.
+ */
+#[derive(Clone)]
+pub struct OnsetDetector<T = f32> {
+    fast_attack: T,
+    fast_release: T,
+    slow: T,
+    fast_envelope: T,
+    slow_envelope: T,
+    threshold: T,
+    refractory_samples: usize,
+    num_samples_until_ready: usize,
+}
+
+impl<T: Sample> OnsetDetector<T> {
+    pub fn new(sample_rate: f32) -> OnsetDetector<T> {
+        let mut detector = OnsetDetector {
+            fast_attack: one_pole_coefficient(FAST_ATTACK, sample_rate),
+            fast_release: one_pole_coefficient(FAST_RELEASE, sample_rate),
+            slow: one_pole_coefficient(SLOW_TIME, sample_rate),
+            fast_envelope: T::zero(),
+            slow_envelope: T::zero(),
+            threshold: T::zero(),
+            refractory_samples: 0,
+            num_samples_until_ready: 0,
+        };
+        detector.set_sensitivity(0.5);
+        detector
+    }
+
+    pub fn reset(&mut self) {
+        self.fast_envelope = T::zero();
+        self.slow_envelope = T::zero();
+        self.num_samples_until_ready = 0;
+    }
+
+    /**
+     * Returns true on the sample a transient is detected.
+     */
+    pub fn process<const N: usize>(&mut self, frame: Frame<T, N>) -> bool {
+        let level = frame
+            .iter()
+            .fold(T::zero(), |level, sample| level.max(sample.abs()));
+        let fast = if level > self.fast_envelope {
+            self.fast_attack
+        } else {
+            self.fast_release
+        };
+        self.fast_envelope += fast * (level - self.fast_envelope);
+        self.slow_envelope += self.slow * (level - self.slow_envelope);
+
+        if self.num_samples_until_ready > 0 {
+            self.num_samples_until_ready -= 1;
+            return false;
+        }
+        let is_onset = self.fast_envelope > T::from_f32(NOISE_FLOOR)
+            && self.fast_envelope > self.slow_envelope * self.threshold;
+        if is_onset {
+            self.num_samples_until_ready = self.refractory_samples;
+        }
+        is_onset
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        let sensitivity = sensitivity.clamp(0.0, 1.0);
+        self.threshold = T::from_f32((1.0 + 3.0 * (1.0 - sensitivity)).exp2());
+    }
+
+    pub fn set_refractory_samples(&mut self, refractory_samples: usize) {
+        self.refractory_samples = refractory_samples;
+    }
+}
+
+/**
+ * Grains started by one onset, evenly spaced in time.
+ */
+#[derive(Clone)]
+pub struct Burst {
+    num_grains_left: usize,
+    interval: f32,
+    num_samples_until_grain: f32,
+}
+
+impl Burst {
+    pub fn new() -> Burst {
+        Burst {
+            num_grains_left: 0,
+            interval: 0.0,
+            num_samples_until_grain: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.num_grains_left = 0;
+    }
+
+    /**
+     * Starts a burst of `num_grains`, one every `interval` samples.
+     */
+    pub fn start(&mut self, num_grains: usize, interval: f32) {
+        self.num_grains_left = num_grains;
+        self.interval = interval;
+        self.num_samples_until_grain = 0.0;
+    }
+
+    /**
+     * When a grain of the burst should start, returns the fraction of a
+     * sample by which it precedes the current sample.
+     */
+    pub fn advance(&mut self) -> Option<f32> {
+        if self.num_grains_left == 0 {
+            return None;
+        }
+        if self.num_samples_until_grain > 0.0 {
+            self.num_samples_until_grain -= 1.0;
+            return None;
+        }
+        let lateness = (-self.num_samples_until_grain).min(1.0);
+        self.num_grains_left -= 1;
+        self.num_samples_until_grain += self.interval - 1.0;
+        Some(lateness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Burst, OnsetDetector};
+
+    fn hit(i: usize) -> f32 {
+        let t = i as f32 / 44100.0;
+        (2.0 * std::f32::consts::PI * 200.0 * t).sin() * (-t / 0.05).exp()
+    }
+
+    #[test]
+    fn detects_each_hit_once() {
+        let mut detector: OnsetDetector = OnsetDetector::new(44100.0);
+        detector.set_refractory_samples(4410);
+        let num_onsets = (0..4 * 22050)
+            .filter(|i| {
+                let sample = hit(i % 22050);
+                detector.process([sample, sample])
+            })
+            .count();
+        assert_eq!(num_onsets, 4);
+    }
+
+    #[test]
+    fn ignores_steady_signal() {
+        let mut detector: OnsetDetector = OnsetDetector::new(44100.0);
+        detector.set_sensitivity(1.0);
+        let mut process = |i: usize| detector.process([(i as f32 * 0.05).sin() * 0.5]);
+        // Lets the slow envelope settle on the signal's level.
+        (0..44100).for_each(|i| {
+            process(i);
+        });
+        let num_onsets = (44100..88200).filter(|i| process(*i)).count();
+        assert_eq!(num_onsets, 0);
+    }
+
+    #[test]
+    fn spaces_burst_grains() {
+        let mut burst = Burst::new();
+        burst.start(3, 10.0);
+        let starts: Vec<usize> = (0..100).filter(|_| burst.advance().is_some()).collect();
+        assert_eq!(starts, vec![0, 10, 20]);
+
+        burst.start(3, 2.5);
+        let starts: Vec<f32> = (0..100)
+            .filter_map(|i| Some(i as f32 - burst.advance()?))
+            .collect();
+        assert_eq!(starts, vec![0.0, 2.5, 5.0]);
+    }
+}
@@ -1,34 +1,36 @@
+use crate::sample::Sample;
+
 #[derive(Copy, Clone)]
-pub struct ParabolicEnvelope {
-    amplitude: f32,
-    slope: f32,
-    curve: f32,
+pub struct ParabolicEnvelope<T = f32> {
+    amplitude: T,
+    slope: T,
+    curve: T,
 
-    duration_samples: f32,
-    grain_amplitude: f32,
+    duration_samples: T,
+    grain_amplitude: T,
 }
 
-impl ParabolicEnvelope {
-    pub fn new(duration_samples: f32, grain_amplitude: f32) -> ParabolicEnvelope {
-        let duration = 1.0 / duration_samples;
+impl<T: Sample> ParabolicEnvelope<T> {
+    pub fn new(duration_samples: T, grain_amplitude: T) -> ParabolicEnvelope<T> {
+        let duration = T::one() / duration_samples;
         let duration2 = duration * duration;
-        let slope = 4.0 * grain_amplitude * (duration - duration2);
+        let slope = T::from_f32(4.0) * grain_amplitude * (duration - duration2);
 
         ParabolicEnvelope {
-            amplitude: 0.0,
+            amplitude: T::zero(),
             slope,
-            curve: -8.0 * grain_amplitude * duration2,
+            curve: T::from_f32(-8.0) * grain_amplitude * duration2,
 
             duration_samples,
             grain_amplitude,
         }
     }
 
-    pub fn process(&mut self) -> f32 {
-        self.amplitude = self.amplitude + self.slope;
-        self.slope = self.slope + self.curve;
+    pub fn process(&mut self) -> T {
+        self.amplitude += self.slope;
+        self.slope += self.curve;
 
-        if self.amplitude < 0.0 {
+        if self.amplitude < T::zero() {
             let new = ParabolicEnvelope::new(self.duration_samples, self.grain_amplitude);
             self.amplitude = new.amplitude;
             self.slope = new.slope;
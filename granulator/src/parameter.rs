@@ -0,0 +1,818 @@
+use crate::filter::FLAT_RESONANCE;
+
+/**
+ * Every parameter of the engine. Plugin wrappers, UIs, MIDI mapping and
+ * presets can be generated from this list and the descriptors below.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParameterId {
+    Mode,
+    Position,
+    Density,
+    Overlap,
+    GrainTrigger,
+    OnsetSensitivity,
+    OnsetRefractory,
+    OnsetBurst,
+    Tempo,
+    PatternDivision,
+    Duration,
+    Pitch,
+    GrainSpread,
+    ZeroCrossingWindow,
+    GrainFilter,
+    GrainFilterCutoff,
+    GrainFilterCutoffSpread,
+    GrainFilterResonance,
+    GrainFilterResonanceSpread,
+    GrainFilterSweep,
+    Volume,
+    Feedback,
+    FeedbackHighPass,
+    FeedbackLowPass,
+    FeedbackDcBlocker,
+    FeedbackSaturation,
+    WetDry,
+    CrossfadeLaw,
+    DryLevel,
+    WetLevel,
+    DryLatencyCompensation,
+    Diffusion,
+    ReverbRouting,
+    ReverbAmount,
+    ReverbRoomSize,
+    ReverbDamping,
+    ReverbWidth,
+    ReverbFreeze,
+    SpectralFreeze,
+    SpectralPhaseRandomization,
+    SpectralQuantization,
+    EnvelopeAttack,
+    EnvelopeRelease,
+    EnvelopeGain,
+    EnvelopeToDensity,
+    EnvelopeToVolume,
+    EnvelopeToFeedback,
+    EnvelopeToPosition,
+    MorphX,
+    MorphY,
+}
+
+pub const NUM_PARAMETERS: usize = 50;
+
+impl ParameterId {
+    pub const ALL: [ParameterId; NUM_PARAMETERS] = [
+        ParameterId::Mode,
+        ParameterId::Position,
+        ParameterId::Density,
+        ParameterId::Overlap,
+        ParameterId::GrainTrigger,
+        ParameterId::OnsetSensitivity,
+        ParameterId::OnsetRefractory,
+        ParameterId::OnsetBurst,
+        ParameterId::Tempo,
+        ParameterId::PatternDivision,
+        ParameterId::Duration,
+        ParameterId::Pitch,
+        ParameterId::GrainSpread,
+        ParameterId::ZeroCrossingWindow,
+        ParameterId::GrainFilter,
+        ParameterId::GrainFilterCutoff,
+        ParameterId::GrainFilterCutoffSpread,
+        ParameterId::GrainFilterResonance,
+        ParameterId::GrainFilterResonanceSpread,
+        ParameterId::GrainFilterSweep,
+        ParameterId::Volume,
+        ParameterId::Feedback,
+        ParameterId::FeedbackHighPass,
+        ParameterId::FeedbackLowPass,
+        ParameterId::FeedbackDcBlocker,
+        ParameterId::FeedbackSaturation,
+        ParameterId::WetDry,
+        ParameterId::CrossfadeLaw,
+        ParameterId::DryLevel,
+        ParameterId::WetLevel,
+        ParameterId::DryLatencyCompensation,
+        ParameterId::Diffusion,
+        ParameterId::ReverbRouting,
+        ParameterId::ReverbAmount,
+        ParameterId::ReverbRoomSize,
+        ParameterId::ReverbDamping,
+        ParameterId::ReverbWidth,
+        ParameterId::ReverbFreeze,
+        ParameterId::SpectralFreeze,
+        ParameterId::SpectralPhaseRandomization,
+        ParameterId::SpectralQuantization,
+        ParameterId::EnvelopeAttack,
+        ParameterId::EnvelopeRelease,
+        ParameterId::EnvelopeGain,
+        ParameterId::EnvelopeToDensity,
+        ParameterId::EnvelopeToVolume,
+        ParameterId::EnvelopeToFeedback,
+        ParameterId::EnvelopeToPosition,
+        ParameterId::MorphX,
+        ParameterId::MorphY,
+    ];
+
+    pub fn from_index(index: usize) -> Option<ParameterId> {
+        ParameterId::ALL.get(index).copied()
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn descriptor(self) -> &'static ParameterDescriptor {
+        &DESCRIPTORS[self.index()]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Unit {
+    None,
+    Samples,
+    Hertz,
+    Milliseconds,
+    Decibels,
+    Octaves,
+    BeatsPerMinute,
+    // Playback rate, 1.0 is the original pitch.
+    Ratio,
+    Percent,
+}
+
+impl Unit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::None => "",
+            Unit::Samples => "smp",
+            Unit::Hertz => "Hz",
+            Unit::Milliseconds => "ms",
+            Unit::Decibels => "dB",
+            Unit::Octaves => "oct",
+            Unit::BeatsPerMinute => "BPM",
+            Unit::Ratio => "x",
+            Unit::Percent => "%",
+        }
+    }
+}
+
+/**
+ * Mapping between the normalized range 0.0 - 1.0 and plain values.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Taper {
+    Linear,
+    // Equal ratios for equal steps, for frequencies, rates and durations.
+    Logarithmic,
+    // Squared, more resolution at the bottom of the range, for levels.
+    Exponential,
+    // Whole numbers from min to max, for switches and choices.
+    Stepped,
+}
+
+pub struct ParameterDescriptor {
+    pub id: ParameterId,
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    pub unit: Unit,
+    pub taper: Taper,
+    // Plain value at which the corresponding processing stage is bypassed,
+    // either in the range or just outside it, see `clamp`.
+    pub off: Option<f32>,
+    // Formats a plain value for display, without the unit.
+    pub format: fn(f32) -> String,
+}
+
+impl ParameterDescriptor {
+    /**
+     * Clamps to the range and rounds stepped values. When `off` lies
+     * outside the range, values beyond it are off and values between it
+     * and the range are clamped into the range.
+     */
+    pub fn clamp(&self, plain: f32) -> f32 {
+        match self.off {
+            Some(off) if off < self.min && plain <= off => return off,
+            Some(off) if off > self.max && plain >= off => return off,
+            _ => {}
+        }
+        let plain = plain.clamp(self.min, self.max);
+        match self.taper {
+            Taper::Stepped => plain.round(),
+            _ => plain,
+        }
+    }
+
+    /**
+     * Ends of the normalized range: the range itself, extended to `off`
+     * when it lies outside.
+     */
+    fn normalized_range(&self) -> (f32, f32) {
+        match self.off {
+            Some(off) if off < self.min => (off, self.max),
+            Some(off) if off > self.max => (self.min, off),
+            _ => (self.min, self.max),
+        }
+    }
+
+    pub fn normalize(&self, plain: f32) -> f32 {
+        let plain = self.clamp(plain);
+        let (min, max) = self.normalized_range();
+        let normalized = match self.taper {
+            Taper::Linear | Taper::Stepped => (plain - min) / (max - min),
+            Taper::Logarithmic => (plain / min).ln() / (max / min).ln(),
+            Taper::Exponential => ((plain - min) / (max - min)).sqrt(),
+        };
+        normalized.clamp(0.0, 1.0)
+    }
+
+    pub fn denormalize(&self, normalized: f32) -> f32 {
+        let normalized = normalized.clamp(0.0, 1.0);
+        let (min, max) = self.normalized_range();
+        let plain = match self.taper {
+            Taper::Linear | Taper::Stepped => min + normalized * (max - min),
+            Taper::Logarithmic => min * (max / min).powf(normalized),
+            Taper::Exponential => min + normalized * normalized * (max - min),
+        };
+        self.clamp(plain)
+    }
+
+    pub fn is_off(&self, plain: f32) -> bool {
+        self.off == Some(self.clamp(plain))
+    }
+
+    /**
+     * Plain value formatted for display, followed by the unit.
+     */
+    pub fn display(&self, plain: f32) -> String {
+        if self.is_off(plain) {
+            return "Off".to_string();
+        }
+        let value = (self.format)(self.clamp(plain));
+        match self.unit {
+            Unit::None => value,
+            unit => format!("{} {}", value, unit.symbol()),
+        }
+    }
+
+    const fn bypassed_at(mut self, off: f32) -> Self {
+        self.off = Some(off);
+        self
+    }
+}
+
+fn format_decimal(plain: f32) -> String {
+    format!("{:.2}", plain)
+}
+
+fn format_whole(plain: f32) -> String {
+    format!("{:.0}", plain)
+}
+
+fn format_percent(plain: f32) -> String {
+    format!("{:.0}", plain * 100.0)
+}
+
+fn format_switch(plain: f32) -> String {
+    if plain >= 0.5 { "On" } else { "Off" }.to_string()
+}
+
+fn format_choice(names: &[&str], plain: f32) -> String {
+    names
+        .get(plain.round() as usize)
+        .copied()
+        .unwrap_or("")
+        .to_string()
+}
+
+fn format_mode(plain: f32) -> String {
+    format_choice(&["Cloud", "Loop", "Spectral"], plain)
+}
+
+fn format_crossfade_law(plain: f32) -> String {
+    format_choice(&["Linear", "Equal power"], plain)
+}
+
+fn format_grain_trigger(plain: f32) -> String {
+    format_choice(
+        &["Scheduler", "Scheduler and onsets", "Onsets", "Pattern"],
+        plain,
+    )
+}
+
+fn format_grain_filter(plain: f32) -> String {
+    format_choice(&["Off", "Lowpass", "Bandpass", "Highpass"], plain)
+}
+
+fn format_reverb_routing(plain: f32) -> String {
+    format_choice(&["Bypass", "Grains", "Output", "Feedback"], plain)
+}
+
+const fn descriptor(
+    id: ParameterId,
+    name: &'static str,
+    (min, max, default): (f32, f32, f32),
+    unit: Unit,
+    taper: Taper,
+    format: fn(f32) -> String,
+) -> ParameterDescriptor {
+    ParameterDescriptor {
+        id,
+        name,
+        min,
+        max,
+        default,
+        unit,
+        taper,
+        off: None,
+        format,
+    }
+}
+
+// Defaults match `GranulatorOptions::default()`.
+static DESCRIPTORS: [ParameterDescriptor; NUM_PARAMETERS] = [
+    descriptor(
+        ParameterId::Mode,
+        "Mode",
+        (0.0, 2.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_mode,
+    ),
+    descriptor(
+        ParameterId::Position,
+        "Position",
+        (0.1, 10000.0, 1000.0),
+        Unit::Milliseconds,
+        Taper::Exponential,
+        format_whole,
+    ),
+    descriptor(
+        ParameterId::Density,
+        "Density",
+        (1.0, 100.0, 50.0),
+        Unit::None,
+        Taper::Logarithmic,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::Overlap,
+        "Overlap",
+        (0.5, 16.0, 0.0),
+        Unit::None,
+        Taper::Exponential,
+        format_decimal,
+    )
+    .bypassed_at(0.0),
+    descriptor(
+        ParameterId::GrainTrigger,
+        "Grain trigger",
+        (0.0, 3.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_grain_trigger,
+    ),
+    descriptor(
+        ParameterId::OnsetSensitivity,
+        "Onset sensitivity",
+        (0.0, 1.0, 0.5),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::OnsetRefractory,
+        "Onset refractory time",
+        (10.0, 2000.0, 100.0),
+        Unit::Milliseconds,
+        Taper::Logarithmic,
+        format_whole,
+    ),
+    descriptor(
+        ParameterId::OnsetBurst,
+        "Onset burst",
+        (1.0, 16.0, 1.0),
+        Unit::None,
+        Taper::Stepped,
+        format_whole,
+    ),
+    descriptor(
+        ParameterId::Tempo,
+        "Tempo",
+        (20.0, 300.0, 120.0),
+        Unit::BeatsPerMinute,
+        Taper::Linear,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::PatternDivision,
+        "Pattern division",
+        (1.0, 8.0, 4.0),
+        Unit::None,
+        Taper::Stepped,
+        format_whole,
+    ),
+    descriptor(
+        ParameterId::Duration,
+        "Duration",
+        // 3000 samples at the default sample rate of 41 kHz.
+        (0.2, 10000.0, 3000.0 / 41.0),
+        Unit::Milliseconds,
+        Taper::Logarithmic,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::Pitch,
+        "Pitch",
+        (0.1, 10.0, 1.0),
+        Unit::Ratio,
+        Taper::Logarithmic,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::GrainSpread,
+        "Grain spread",
+        (0.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::ZeroCrossingWindow,
+        "Zero crossing window",
+        (0.0, 10.0, 0.0),
+        Unit::Milliseconds,
+        Taper::Linear,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::GrainFilter,
+        "Grain filter",
+        (0.0, 3.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_grain_filter,
+    ),
+    descriptor(
+        ParameterId::GrainFilterCutoff,
+        "Grain filter cutoff",
+        (20.0, 20000.0, 1000.0),
+        Unit::Hertz,
+        Taper::Logarithmic,
+        format_whole,
+    ),
+    descriptor(
+        ParameterId::GrainFilterCutoffSpread,
+        "Grain filter cutoff spread",
+        (0.0, 4.0, 0.0),
+        Unit::Octaves,
+        Taper::Linear,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::GrainFilterResonance,
+        "Grain filter resonance",
+        (0.5, 20.0, FLAT_RESONANCE),
+        Unit::None,
+        Taper::Logarithmic,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::GrainFilterResonanceSpread,
+        "Grain filter resonance spread",
+        (0.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::GrainFilterSweep,
+        "Grain filter sweep",
+        (-8.0, 8.0, 0.0),
+        Unit::Octaves,
+        Taper::Linear,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::Volume,
+        "Volume",
+        (0.0, 1.0, 0.5),
+        Unit::Percent,
+        Taper::Exponential,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::Feedback,
+        "Feedback",
+        (0.0, 1.0, 0.6),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::FeedbackHighPass,
+        "Feedback high-pass",
+        (20.0, 20000.0, 10.0),
+        Unit::Hertz,
+        Taper::Logarithmic,
+        format_whole,
+    )
+    .bypassed_at(10.0),
+    descriptor(
+        ParameterId::FeedbackLowPass,
+        "Feedback low-pass",
+        (20.0, 20000.0, 40000.0),
+        Unit::Hertz,
+        Taper::Logarithmic,
+        format_whole,
+    )
+    .bypassed_at(40000.0),
+    descriptor(
+        ParameterId::FeedbackDcBlocker,
+        "Feedback DC blocker",
+        (0.0, 1.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_switch,
+    ),
+    descriptor(
+        ParameterId::FeedbackSaturation,
+        "Feedback saturation",
+        (0.0, 10.0, 0.0),
+        Unit::None,
+        Taper::Linear,
+        format_decimal,
+    )
+    .bypassed_at(0.0),
+    descriptor(
+        ParameterId::WetDry,
+        "Wet/dry",
+        (0.0, 1.0, 1.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::CrossfadeLaw,
+        "Crossfade law",
+        (0.0, 1.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_crossfade_law,
+    ),
+    descriptor(
+        ParameterId::DryLevel,
+        "Dry level",
+        (0.0, 1.0, 1.0),
+        Unit::Percent,
+        Taper::Exponential,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::WetLevel,
+        "Wet level",
+        (0.0, 1.0, 1.0),
+        Unit::Percent,
+        Taper::Exponential,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::DryLatencyCompensation,
+        "Dry latency compensation",
+        (0.0, 1.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_switch,
+    ),
+    descriptor(
+        ParameterId::Diffusion,
+        "Diffusion",
+        (0.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::ReverbRouting,
+        "Reverb routing",
+        (0.0, 3.0, 2.0),
+        Unit::None,
+        Taper::Stepped,
+        format_reverb_routing,
+    ),
+    descriptor(
+        ParameterId::ReverbAmount,
+        "Reverb amount",
+        (0.0, 1.0, 1.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::ReverbRoomSize,
+        "Reverb room size",
+        (0.0, 1.0, 0.1),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::ReverbDamping,
+        "Reverb damping",
+        (0.0, 1.0, 0.1),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::ReverbWidth,
+        "Reverb width",
+        (0.0, 1.0, 0.1),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::ReverbFreeze,
+        "Reverb freeze",
+        (0.0, 1.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_switch,
+    ),
+    descriptor(
+        ParameterId::SpectralFreeze,
+        "Spectral freeze",
+        (0.0, 1.0, 0.0),
+        Unit::None,
+        Taper::Stepped,
+        format_switch,
+    ),
+    descriptor(
+        ParameterId::SpectralPhaseRandomization,
+        "Spectral phase randomization",
+        (0.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::SpectralQuantization,
+        "Spectral quantization",
+        (0.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::EnvelopeAttack,
+        "Envelope attack",
+        (0.1, 1000.0, 10.0),
+        Unit::Milliseconds,
+        Taper::Logarithmic,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::EnvelopeRelease,
+        "Envelope release",
+        (1.0, 5000.0, 200.0),
+        Unit::Milliseconds,
+        Taper::Logarithmic,
+        format_whole,
+    ),
+    descriptor(
+        ParameterId::EnvelopeGain,
+        "Envelope gain",
+        (-12.0, 36.0, 0.0),
+        Unit::Decibels,
+        Taper::Linear,
+        format_decimal,
+    ),
+    descriptor(
+        ParameterId::EnvelopeToDensity,
+        "Envelope to density",
+        (-1.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::EnvelopeToVolume,
+        "Envelope to volume",
+        (-1.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::EnvelopeToFeedback,
+        "Envelope to feedback",
+        (-1.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::EnvelopeToPosition,
+        "Envelope to position",
+        (-1.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::MorphX,
+        "Morph X",
+        (0.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+    descriptor(
+        ParameterId::MorphY,
+        "Morph Y",
+        (0.0, 1.0, 0.0),
+        Unit::Percent,
+        Taper::Linear,
+        format_percent,
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::ParameterId;
+
+    #[test]
+    fn descriptors_match_ids() {
+        for (index, id) in ParameterId::ALL.iter().enumerate() {
+            assert_eq!(id.index(), index);
+            assert_eq!(id.descriptor().id, *id);
+            assert_eq!(ParameterId::from_index(index), Some(*id));
+        }
+        assert_eq!(ParameterId::from_index(ParameterId::ALL.len()), None);
+    }
+
+    #[test]
+    fn normalization_round_trips() {
+        for id in ParameterId::ALL.iter() {
+            let descriptor = id.descriptor();
+            for plain in [descriptor.min, descriptor.default, descriptor.max].iter() {
+                let restored = descriptor.denormalize(descriptor.normalize(*plain));
+                assert!((restored - plain).abs() <= plain.abs() * 1e-4 + 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn logarithmic_taper_centre_is_geometric_mean() {
+        let pitch = ParameterId::Pitch.descriptor();
+        assert!((pitch.denormalize(0.5) - 1.0).abs() < 1e-5);
+        assert!((pitch.normalize(1.0) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn display_with_units() {
+        assert_eq!(ParameterId::Pitch.descriptor().display(2.0), "2.00 x");
+        assert_eq!(ParameterId::Volume.descriptor().display(0.5), "50 %");
+        assert_eq!(ParameterId::Mode.descriptor().display(1.0), "Loop");
+        assert_eq!(
+            ParameterId::FeedbackLowPass.descriptor().display(40000.0),
+            "Off"
+        );
+        assert_eq!(
+            ParameterId::FeedbackLowPass.descriptor().display(20000.0),
+            "20000 Hz"
+        );
+        assert_eq!(
+            ParameterId::FeedbackLowPass.descriptor().display(440.0),
+            "440 Hz"
+        );
+    }
+
+    #[test]
+    fn off_outside_range_is_kept_apart() {
+        let overlap = ParameterId::Overlap.descriptor();
+        assert!(overlap.is_off(0.0));
+        assert_eq!(overlap.clamp(0.2), 0.5);
+        assert!(!overlap.is_off(0.2));
+        assert_eq!(overlap.denormalize(0.0), 0.0);
+        assert_eq!(overlap.denormalize(0.1), 0.5);
+        assert_eq!(overlap.normalize(0.0), 0.0);
+        assert_eq!(overlap.denormalize(1.0), 16.0);
+
+        let high_pass = ParameterId::FeedbackHighPass.descriptor();
+        assert!(high_pass.is_off(10.0));
+        assert!(!high_pass.is_off(20.0));
+        assert_eq!(high_pass.clamp(15.0), 20.0);
+        assert_eq!(high_pass.denormalize(0.0), 10.0);
+        assert_eq!(high_pass.normalize(10.0), 0.0);
+
+        let low_pass = ParameterId::FeedbackLowPass.descriptor();
+        assert!(low_pass.is_off(40000.0));
+        assert!(!low_pass.is_off(20000.0));
+        assert_eq!(low_pass.clamp(30000.0), 20000.0);
+        assert_eq!(low_pass.denormalize(1.0), 40000.0);
+    }
+}
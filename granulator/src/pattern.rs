@@ -0,0 +1,265 @@
+use rand::Rng;
+
+pub const MAX_PATTERN_STEPS: usize = 64;
+// Steps due within rounding errors of the clock play on time.
+const PHASE_TOLERANCE: f64 = 1e-9;
+
+/**
+ * One step of a `Pattern`: the chance of a grain on that step and the
+ * offsets applied to it.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PatternStep {
+    // 0.0 - never, 1.0 - always.
+    pub probability: f32,
+    // Transposition of the grain, in semitones.
+    pub pitch: f32,
+    // Added to the grain position, in samples.
+    pub position: f32,
+    // Position across the output channels, 0.0 - N. Random when `None`.
+    pub pan: Option<f32>,
+}
+
+impl PatternStep {
+    pub const REST: PatternStep = PatternStep {
+        probability: 0.0,
+        pitch: 0.0,
+        position: 0.0,
+        pan: None,
+    };
+
+    pub const HIT: PatternStep = PatternStep {
+        probability: 1.0,
+        ..PatternStep::REST
+    };
+}
+
+/**
+ * Step sequence of up to `MAX_PATTERN_STEPS` steps, stored inline so that
+ * it can be replaced on the audio thread.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pattern {
+    steps: [PatternStep; MAX_PATTERN_STEPS],
+    num_steps: usize,
+}
+
+impl Pattern {
+    /**
+     * `hits` evenly spread over `num_steps` steps, the first one on step
+     * `rotation`. `Pattern::euclidean(3, 8, 0)` is x..x..x.
+     */
+    pub fn euclidean(hits: usize, num_steps: usize, rotation: usize) -> Pattern {
+        let num_steps = num_steps.clamp(1, MAX_PATTERN_STEPS);
+        let mut steps = [PatternStep::REST; MAX_PATTERN_STEPS];
+        for step in 0..num_steps {
+            if (step * hits) % num_steps < hits {
+                steps[(step + rotation) % num_steps] = PatternStep::HIT;
+            }
+        }
+        Pattern { steps, num_steps }
+    }
+
+    /**
+     * One step per probability, up to `MAX_PATTERN_STEPS`.
+     */
+    pub fn from_probabilities(probabilities: &[f32]) -> Pattern {
+        let mut steps = [PatternStep::REST; MAX_PATTERN_STEPS];
+        for (step, probability) in steps.iter_mut().zip(probabilities.iter()) {
+            step.probability = *probability;
+        }
+        Pattern {
+            steps,
+            num_steps: probabilities.len().clamp(1, MAX_PATTERN_STEPS),
+        }
+    }
+
+    pub fn steps(&self) -> &[PatternStep] {
+        &self.steps[..self.num_steps]
+    }
+
+    /**
+     * For setting per-step probabilities and offsets.
+     */
+    pub fn steps_mut(&mut self) -> &mut [PatternStep] {
+        &mut self.steps[..self.num_steps]
+    }
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Pattern::euclidean(4, 16, 0)
+    }
+}
+
+/**
+ * Plays a `Pattern` on a tempo clock, in the role of the `Scheduler`.
+ */
+#[derive(Copy, Clone)]
+pub struct Sequencer {
+    pattern: Pattern,
+    step: usize,
+    // Progress towards the next step, a step is due at 1.0. Double
+    // precision keeps the clock from drifting over long sessions.
+    phase: f64,
+    sample_rate: f32,
+    tempo: f32,
+    division: f32,
+    // Steps per sample.
+    increment: f64,
+}
+
+impl Sequencer {
+    pub fn new(sample_rate: f32) -> Sequencer {
+        let mut sequencer = Sequencer {
+            pattern: Pattern::default(),
+            step: 0,
+            phase: 1.0,
+            sample_rate,
+            tempo: 120.0,
+            division: 4.0,
+            increment: 0.0,
+        };
+        sequencer.update_increment();
+        sequencer
+    }
+
+    /**
+     * Starts again from the first step.
+     */
+    pub fn reset(&mut self) {
+        self.step = 0;
+        self.phase = 1.0;
+    }
+
+    /**
+     * Advances the clock by one sample and returns the step due on it,
+     * if its grain should play, with the fraction of a sample by which
+     * the step precedes the current sample.
+     */
+    pub fn advance(&mut self, rng: &mut impl Rng) -> Option<(PatternStep, f32)> {
+        let mut due = None;
+        if self.phase >= 1.0 - PHASE_TOLERANCE {
+            self.phase -= 1.0;
+            let lateness = if self.increment > 0.0 {
+                (self.phase / self.increment).clamp(0.0, 1.0) as f32
+            } else {
+                0.0
+            };
+            let step = self.pattern.steps[self.step];
+            self.step = (self.step + 1) % self.pattern.num_steps;
+            let plays = match step.probability {
+                probability if probability >= 1.0 => true,
+                probability if probability <= 0.0 => false,
+                probability => rng.gen::<f32>() < probability,
+            };
+            if plays {
+                due = Some((step, lateness));
+            }
+        }
+        self.phase += self.increment;
+        due
+    }
+
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    /**
+     * Keeps the position in the bar when the new pattern is long enough.
+     */
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        self.pattern = pattern;
+        self.step %= pattern.num_steps;
+    }
+
+    /**
+     * Beats per minute.
+     */
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.tempo = tempo;
+        self.update_increment();
+    }
+
+    /**
+     * Steps per beat, 4 plays sixteenth notes.
+     */
+    pub fn set_division(&mut self, division: f32) {
+        self.division = division;
+        self.update_increment();
+    }
+
+    fn update_increment(&mut self) {
+        let steps_per_second = self.tempo as f64 / 60.0 * self.division as f64;
+        self.increment = (steps_per_second / self.sample_rate as f64).max(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pattern, Sequencer};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn hits(pattern: &Pattern) -> Vec<bool> {
+        pattern
+            .steps()
+            .iter()
+            .map(|step| step.probability == 1.0)
+            .collect()
+    }
+
+    #[test]
+    fn euclidean_spreads_hits() {
+        let (x, o) = (true, false);
+        assert_eq!(hits(&Pattern::euclidean(3, 8, 0)), [x, o, o, x, o, o, x, o]);
+        assert_eq!(hits(&Pattern::euclidean(3, 8, 2)), [x, o, x, o, o, x, o, o]);
+        assert_eq!(hits(&Pattern::euclidean(4, 4, 0)), [x, x, x, x]);
+        assert_eq!(hits(&Pattern::euclidean(0, 3, 0)), [o, o, o]);
+    }
+
+    #[test]
+    fn plays_steps_on_the_clock() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // 8 steps per second at 1024 Hz, one step every 128 samples.
+        let mut sequencer = Sequencer::new(1024.0);
+        sequencer.set_tempo(120.0);
+        sequencer.set_division(4.0);
+        sequencer.set_pattern(Pattern::euclidean(3, 8, 0));
+        let onsets: Vec<usize> = (0..2048)
+            .filter(|_| sequencer.advance(&mut rng).is_some())
+            .collect();
+        assert_eq!(onsets, [0, 384, 768, 1024, 1408, 1792]);
+    }
+
+    #[test]
+    fn follows_step_probabilities() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut sequencer = Sequencer::new(800.0);
+        sequencer.set_tempo(6000.0);
+        sequencer.set_division(8.0);
+        sequencer.set_pattern(Pattern::from_probabilities(&[0.25, 0.0]));
+        // One step per sample.
+        let num_grains = (0..20000)
+            .filter(|_| sequencer.advance(&mut rng).is_some())
+            .count();
+        assert!((num_grains as f32 - 2500.0).abs() < 150.0);
+    }
+
+    #[test]
+    fn steps_fall_between_samples() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // One step every 2.5 samples.
+        let mut sequencer = Sequencer::new(1000.0);
+        sequencer.set_tempo(6000.0);
+        sequencer.set_division(4.0);
+        sequencer.set_pattern(Pattern::euclidean(1, 1, 0));
+        let onsets: Vec<(usize, f32)> = (0..10)
+            .filter_map(|i| Some((i, sequencer.advance(&mut rng)?.1)))
+            .collect();
+        assert_eq!(onsets.len(), 4);
+        for ((i, lateness), expected) in onsets.iter().zip([0.0, 2.5, 5.0, 7.5]) {
+            assert!((*i as f32 - lateness - expected).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::granulator::GranulatorOptions;
+
+// Increased whenever the meaning of a stored parameter changes.
+pub const PRESET_VERSION: u32 = 1;
+
+fn current_version() -> u32 {
+    PRESET_VERSION
+}
+
+/**
+ * Musical state of the engine: every parameter, hooks excluded.
+ * Missing parameters get their default value and unknown ones are ignored,
+ * so presets stay loadable across versions.
+ */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(default = "current_version")]
+    pub version: u32,
+    // Sample rate the positions and durations were stored at, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<usize>,
+    #[serde(flatten)]
+    pub options: GranulatorOptions,
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    Json(serde_json::Error),
+    TomlSerialize(toml::ser::Error),
+    TomlDeserialize(toml::de::Error),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PresetError::Json(error) => write!(f, "invalid JSON preset: {}", error),
+            PresetError::TomlSerialize(error) => write!(f, "can't write TOML preset: {}", error),
+            PresetError::TomlDeserialize(error) => write!(f, "invalid TOML preset: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+impl Preset {
+    pub fn new(options: GranulatorOptions) -> Preset {
+        Preset {
+            version: PRESET_VERSION,
+            sample_rate: None,
+            options,
+        }
+    }
+
+    pub fn with_sample_rate(options: GranulatorOptions, sample_rate: usize) -> Preset {
+        Preset {
+            sample_rate: Some(sample_rate),
+            ..Preset::new(options)
+        }
+    }
+
+    /**
+     * Options with positions and durations converted to `sample_rate`,
+     * so that they keep their length in time.
+     */
+    pub fn options_at(&self, sample_rate: usize) -> GranulatorOptions {
+        let mut options = self.options.clone();
+        if let Some(preset_sample_rate) = self.sample_rate {
+            let ratio = sample_rate as f32 / preset_sample_rate as f32;
+            options.position *= ratio;
+            options.duration *= ratio;
+        }
+        options
+    }
+
+    pub fn to_json(&self) -> Result<String, PresetError> {
+        serde_json::to_string_pretty(self).map_err(PresetError::Json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Preset, PresetError> {
+        serde_json::from_str(json).map_err(PresetError::Json)
+    }
+
+    pub fn to_toml(&self) -> Result<String, PresetError> {
+        toml::to_string(self).map_err(PresetError::TomlSerialize)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Preset, PresetError> {
+        toml::from_str(toml).map_err(PresetError::TomlDeserialize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Preset, PRESET_VERSION};
+    use crate::granulator::{GranulatorOptions, Mode};
+
+    fn preset() -> Preset {
+        Preset::new(GranulatorOptions {
+            mode: Mode::Loop,
+            density: 12.5,
+            feedback_low_pass: Some(2000.0),
+            ..GranulatorOptions::default()
+        })
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let restored = Preset::from_json(&preset().to_json().unwrap()).unwrap();
+        assert_eq!(restored.version, PRESET_VERSION);
+        assert_eq!(restored.options.mode, Mode::Loop);
+        assert_eq!(restored.options.density, 12.5);
+        assert_eq!(restored.options.feedback_low_pass, Some(2000.0));
+        assert_eq!(restored.options.feedback_high_pass, None);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let restored = Preset::from_toml(&preset().to_toml().unwrap()).unwrap();
+        assert_eq!(restored.options.mode, Mode::Loop);
+        assert_eq!(restored.options.density, 12.5);
+        assert_eq!(restored.options.feedback_low_pass, Some(2000.0));
+    }
+
+    #[test]
+    fn keeps_time_across_sample_rates() {
+        let options = GranulatorOptions {
+            position: 44100.0,
+            duration: 441.0,
+            ..GranulatorOptions::default()
+        };
+        let json = Preset::with_sample_rate(options, 44100).to_json().unwrap();
+        let restored = Preset::from_json(&json).unwrap().options_at(48000);
+        assert!((restored.position - 48000.0).abs() < 1e-2);
+        assert!((restored.duration - 480.0).abs() < 1e-3);
+        assert_eq!(preset().options_at(48000).density, 12.5);
+    }
+
+    #[test]
+    fn missing_and_unknown_fields() {
+        let restored = Preset::from_toml("pitch = 2.0\nfuture_parameter = 1.0\n").unwrap();
+        assert_eq!(restored.version, PRESET_VERSION);
+        assert_eq!(restored.options.pitch, 2.0);
+        assert_eq!(
+            restored.options.density,
+            GranulatorOptions::default().density
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use freeverb::Freeverb;
+
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+/**
+ * Freeverb on any number of channels. Channels are processed in pairs,
+ * each by its own stereo reverb, a remaining single channel feeds both
+ * inputs of the last reverb and takes its left output.
+ */
+pub struct Reverb<T = f32, const N: usize = 2> {
+    freeverbs: Vec<Freeverb<T>>,
+}
+
+// `clone_from` reuses the reverb buffers, so that restoring a state doesn't allocate.
+impl<T: Sample, const N: usize> Clone for Reverb<T, N> {
+    fn clone(&self) -> Self {
+        Reverb {
+            freeverbs: self.freeverbs.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.freeverbs.clone_from(&source.freeverbs);
+    }
+}
+
+impl<T: Sample, const N: usize> Reverb<T, N> {
+    pub fn new(sample_rate: usize) -> Reverb<T, N> {
+        Reverb {
+            freeverbs: (0..N.div_ceil(2))
+                .map(|_| Freeverb::new(sample_rate))
+                .collect(),
+        }
+    }
+
+    pub fn process(&mut self, frame: Frame<T, N>) -> Frame<T, N> {
+        let mut output = frame;
+        for (pair, freeverb) in self.freeverbs.iter_mut().enumerate() {
+            let left = 2 * pair;
+            let right = (left + 1).min(N - 1);
+            let (processed_left, processed_right) = freeverb.tick((frame[left], frame[right]));
+            output[left] = processed_left;
+            if right != left {
+                output[right] = processed_right;
+            }
+        }
+        output
+    }
+
+    pub fn reset(&mut self) {
+        for freeverb in self.freeverbs.iter_mut() {
+            freeverb.reset();
+        }
+    }
+
+    pub fn set_wet(&mut self, wet: f32) {
+        for freeverb in self.freeverbs.iter_mut() {
+            freeverb.set_wet(T::from_f32(wet));
+        }
+    }
+
+    pub fn set_dry(&mut self, dry: f32) {
+        for freeverb in self.freeverbs.iter_mut() {
+            freeverb.set_dry(T::from_f32(dry));
+        }
+    }
+
+    pub fn set_room_size(&mut self, room_size: f32) {
+        for freeverb in self.freeverbs.iter_mut() {
+            freeverb.set_room_size(T::from_f32(room_size));
+        }
+    }
+
+    pub fn set_dampening(&mut self, dampening: f32) {
+        for freeverb in self.freeverbs.iter_mut() {
+            freeverb.set_dampening(T::from_f32(dampening));
+        }
+    }
+
+    pub fn set_width(&mut self, width: f32) {
+        for freeverb in self.freeverbs.iter_mut() {
+            freeverb.set_width(T::from_f32(width));
+        }
+    }
+
+    pub fn set_freeze(&mut self, is_frozen: bool) {
+        for freeverb in self.freeverbs.iter_mut() {
+            freeverb.set_freeze(is_frozen);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reverb;
+
+    #[test]
+    fn processes_every_channel() {
+        let mut reverb: Reverb<f32, 3> = Reverb::new(44100);
+        reverb.set_dry(0.0);
+        reverb.process([1.0, 0.0, 1.0]);
+        let mut energy = [0.0; 3];
+        for _ in 0..10000 {
+            let frame = reverb.process([0.0; 3]);
+            for (energy, sample) in energy.iter_mut().zip(frame.iter()) {
+                *energy += sample * sample;
+            }
+        }
+        assert!(energy.iter().all(|energy| *energy > 0.0));
+    }
+}
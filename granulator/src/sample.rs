@@ -0,0 +1,74 @@
+use num_traits::{Float, FloatConst};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::ops::{AddAssign, DivAssign, MulAssign, SubAssign};
+
+/**
+ * Floating point type of the audio signal and of all processing state:
+ * `f32` for embedded and real-time use, `f64` for long feedback sessions
+ * and offline renders. Parameters stay `f32` in both cases.
+ */
+pub trait Sample:
+    Float
+    + FloatConst
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + Sum
+    + Default
+    + Debug
+    + Send
+    + Sync
+    + 'static
+{
+    fn from_f32(value: f32) -> Self;
+
+    fn from_usize(value: usize) -> Self;
+
+    /**
+     * Whole part as an index, 0 for negative values.
+     */
+    fn to_index(self) -> usize;
+
+    /**
+     * Value as a parameter, for metering and modulation.
+     */
+    fn to_parameter(self) -> f32;
+}
+
+impl Sample for f32 {
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn from_usize(value: usize) -> Self {
+        value as f32
+    }
+
+    fn to_index(self) -> usize {
+        self as usize
+    }
+
+    fn to_parameter(self) -> f32 {
+        self
+    }
+}
+
+impl Sample for f64 {
+    fn from_f32(value: f32) -> Self {
+        value as f64
+    }
+
+    fn from_usize(value: usize) -> Self {
+        value as f64
+    }
+
+    fn to_index(self) -> usize {
+        self as usize
+    }
+
+    fn to_parameter(self) -> f32 {
+        self as f32
+    }
+}
@@ -1,47 +1,142 @@
 use rand::Rng;
 
+// Mean of -ln(random) for random uniform in 0.1 - 1.0,
+// see `calculate_next_interonset`.
+const MEAN_RANDOM_INTERONSET: f32 = 0.744157;
+// Lower densities would draw interonsets too long to ever end.
+const MIN_DENSITY: f32 = 1e-6;
+
+#[derive(Clone)]
 pub struct Scheduler {
-    next_onset: usize,
+    next_onset: f32,
     density: f32,
+    // Samples counted down per sample, see `set_rate`.
+    rate: f32,
 }
 
 impl Scheduler {
     pub fn new(density: f32) -> Scheduler {
-        Scheduler {
-            next_onset: 0,
-            density,
-        }
+        let mut scheduler = Scheduler {
+            next_onset: 0.0,
+            density: 1.0,
+            rate: 1.0,
+        };
+        scheduler.set_density(density);
+        scheduler
     }
 
     /**
-     * Advances scheduler and returns a bool telling whether
-     * new grain should be activated.
+     * Advances scheduler and returns, when a new grain should be
+     * activated, the fraction of a sample by which its onset
+     * precedes the current sample.
      */
-    pub fn advance(&mut self) -> bool {
-        if self.next_onset == 0 {
-            self.next_onset += self.calculate_next_interonset();
-            return true;
+    pub fn advance(&mut self, rng: &mut impl Rng) -> Option<f32> {
+        let mut onset = None;
+        if self.next_onset <= 0.0 {
+            let lateness = if self.rate > 0.0 {
+                -self.next_onset / self.rate
+            } else {
+                0.0
+            };
+            onset = Some(lateness.clamp(0.0, 1.0));
+            self.next_onset += self.calculate_next_interonset(rng);
         }
-        self.next_onset -= 1;
+        self.next_onset -= self.rate;
 
-        false
+        onset
     }
 
     /**
-     * Calculates number of samples after which a new grain
-     * should be activated. Calculation is based on density.
+     * Calculates number of samples, not necessarily whole, after which
+     * a new grain should be activated. Calculation is based on density.
      */
-    fn calculate_next_interonset(&self) -> usize {
-        let mut rng = rand::thread_rng();
+    fn calculate_next_interonset(&self, rng: &mut impl Rng) -> f32 {
         let random: f32 = rng.gen_range(0.1..1.0);
-        let interonset = -(random.ln() / self.density * 1000.0).ceil() as usize;
-        if interonset == 0 {
-            return 1;
-        }
-        interonset
+        (-random.ln() / self.density * 1000.0).max(1.0)
     }
 
+    pub fn reset(&mut self) {
+        self.next_onset = 0.0;
+    }
+
+    /**
+     * Densities below `MIN_DENSITY`, or not finite, are ignored.
+     */
     pub fn set_density(&mut self, density: f32) {
-        self.density = density;
+        if density.is_finite() && density >= MIN_DENSITY {
+            self.density = density;
+        }
+    }
+
+    /**
+     * Speed of the scheduler's clock, scales the density without changing
+     * the random interonsets already drawn. 0.0 pauses the scheduler.
+     */
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.max(0.0);
+    }
+
+    /**
+     * Density giving on average `interonset` samples between grains.
+     */
+    pub fn density_for_interonset(interonset: f32) -> f32 {
+        1000.0 * MEAN_RANDOM_INTERONSET / interonset.max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scheduler;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn density_for_interonset_matches_mean() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for interonset in [20.0, 500.0] {
+            let mut scheduler = Scheduler::new(Scheduler::density_for_interonset(interonset));
+            let num_samples = 2_000_000;
+            let num_onsets = (0..num_samples)
+                .filter(|_| scheduler.advance(&mut rng).is_some())
+                .count();
+            let mean = num_samples as f32 / num_onsets as f32;
+            assert!((mean - interonset).abs() < interonset * 0.03);
+        }
+    }
+
+    #[test]
+    fn onsets_fall_between_samples() {
+        // Half the rate plays the same interonsets twice as slowly, which
+        // only holds when onsets keep their fractions of a sample.
+        let onset_times = |rate| {
+            let mut rng = StdRng::seed_from_u64(1);
+            let mut scheduler = Scheduler::new(Scheduler::density_for_interonset(7.3));
+            scheduler.set_rate(rate);
+            (0..2000)
+                .filter_map(|i| {
+                    let lateness = scheduler.advance(&mut rng)?;
+                    Some(i as f32 - lateness)
+                })
+                .collect::<Vec<f32>>()
+        };
+        let full_rate = onset_times(1.0);
+        let half_rate = onset_times(0.5);
+        assert!(full_rate.iter().any(|time| time.fract() != 0.0));
+        for (full_rate, half_rate) in full_rate.iter().zip(half_rate.iter()) {
+            assert!((full_rate * 2.0 - half_rate).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn ignores_invalid_densities() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut scheduler = Scheduler::new(Scheduler::density_for_interonset(10.0));
+        for density in [0.0, -5.0, f32::NAN, f32::INFINITY] {
+            scheduler.set_density(density);
+            let num_onsets = (0..1000)
+                .filter(|_| scheduler.advance(&mut rng).is_some())
+                .count();
+            assert!((50..150).contains(&num_onsets), "{} onsets", num_onsets);
+        }
     }
 }
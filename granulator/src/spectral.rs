@@ -0,0 +1,414 @@
+use rand::Rng;
+use std::f32::consts::PI;
+
+use crate::delay_line::DelayLine;
+use crate::fft::{Complex, Fft};
+use crate::frame::Frame;
+use crate::sample::Sample;
+
+const FFT_SIZE: usize = 2048;
+const HOP_SIZE: usize = FFT_SIZE / 4;
+const NUM_BINS: usize = FFT_SIZE / 2 + 1;
+// Delay added on top of `position` by overlap-add and by spreading the
+// work on each frame over a hop, in samples.
+pub const LATENCY: usize = FFT_SIZE + HOP_SIZE;
+// Overlap-add ring buffer, one hop longer than a frame so that a frame can
+// be added while the previous hop is played.
+const OUTPUT_SIZE: usize = FFT_SIZE + HOP_SIZE;
+// Hann analysis and synthesis windows overlapping 4 times sum up to 1.5.
+const OVERLAP_ADD_GAIN: f32 = 1.0 / 1.5;
+// Density at which every bin is updated on every frame.
+const MAX_DENSITY: f32 = 100.0;
+// Width in bins of the coarsest quantization grid.
+const MAX_QUANTIZATION_STEP: f32 = 32.0;
+
+fn wrap_phase<T: Sample>(phase: T) -> T {
+    phase - T::TAU() * ((phase + T::PI()) / T::TAU()).floor()
+}
+
+/**
+ * Work on one channel of a gathered frame, split so that each step costs at
+ * most one FFT.
+ */
+#[derive(Copy, Clone)]
+enum Stage {
+    Forward,
+    Analyse,
+    Synthesize,
+    Inverse,
+    OverlapAdd,
+}
+
+const STAGES: [Stage; 5] = [
+    Stage::Forward,
+    Stage::Analyse,
+    Stage::Synthesize,
+    Stage::Inverse,
+    Stage::OverlapAdd,
+];
+
+struct SpectralChannel<T> {
+    spectrum: Vec<Complex<T>>,
+    // Analysed, blurred spectrum.
+    magnitudes: Vec<T>,
+    // Instantaneous frequencies, in bins.
+    frequencies: Vec<T>,
+    previous_phases: Vec<T>,
+    // Spectrum after pitch shifting and quantization.
+    shifted_magnitudes: Vec<T>,
+    shifted_frequencies: Vec<T>,
+    synthesis_phases: Vec<T>,
+    // Overlap-add accumulator, read as a ring buffer.
+    output: Vec<T>,
+}
+
+// `clone_from` reuses the buffers, so that restoring a state doesn't allocate.
+impl<T: Sample> Clone for SpectralChannel<T> {
+    fn clone(&self) -> Self {
+        SpectralChannel {
+            spectrum: self.spectrum.clone(),
+            magnitudes: self.magnitudes.clone(),
+            frequencies: self.frequencies.clone(),
+            previous_phases: self.previous_phases.clone(),
+            shifted_magnitudes: self.shifted_magnitudes.clone(),
+            shifted_frequencies: self.shifted_frequencies.clone(),
+            synthesis_phases: self.synthesis_phases.clone(),
+            output: self.output.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.spectrum.clone_from(&source.spectrum);
+        self.magnitudes.clone_from(&source.magnitudes);
+        self.frequencies.clone_from(&source.frequencies);
+        self.previous_phases.clone_from(&source.previous_phases);
+        self.shifted_magnitudes
+            .clone_from(&source.shifted_magnitudes);
+        self.shifted_frequencies
+            .clone_from(&source.shifted_frequencies);
+        self.synthesis_phases.clone_from(&source.synthesis_phases);
+        self.output.clone_from(&source.output);
+    }
+}
+
+impl<T: Sample> SpectralChannel<T> {
+    fn new() -> SpectralChannel<T> {
+        SpectralChannel {
+            spectrum: vec![Complex::zero(); FFT_SIZE],
+            magnitudes: vec![T::zero(); NUM_BINS],
+            frequencies: vec![T::zero(); NUM_BINS],
+            previous_phases: vec![T::zero(); NUM_BINS],
+            shifted_magnitudes: vec![T::zero(); NUM_BINS],
+            shifted_frequencies: vec![T::zero(); NUM_BINS],
+            synthesis_phases: vec![T::zero(); NUM_BINS],
+            output: vec![T::zero(); OUTPUT_SIZE],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.spectrum
+            .iter_mut()
+            .for_each(|value| *value = Complex::zero());
+        for buffer in [
+            &mut self.magnitudes,
+            &mut self.frequencies,
+            &mut self.previous_phases,
+            &mut self.shifted_magnitudes,
+            &mut self.shifted_frequencies,
+            &mut self.synthesis_phases,
+            &mut self.output,
+        ] {
+            buffer.iter_mut().for_each(|value| *value = T::zero());
+        }
+    }
+}
+
+/**
+ * Phase vocoder reading overlapping FFT frames from the delay line.
+ *
+ * Every `HOP_SIZE` samples a frame ending `position` samples behind the
+ * write head is analysed and resynthesized:
+ * - `duration` sets the spectral blur time, in samples,
+ * - `density` sets the probability of a bin being updated by a new frame,
+ * - `pitch` shifts frequencies by moving the content of bins.
+ *
+ * On top of that the spectrum can be frozen, its phases randomized and its
+ * bins quantized to a coarser grid.
+ *
+ * The work on a frame is spread over the first `1 + 5 * N` samples of its
+ * hop: all channels are gathered first, then one `Stage` of one channel
+ * runs per sample. No sample costs more than one FFT, so small host blocks
+ * see an even load. The frame is played from the next hop on, see `LATENCY`.
+ */
+pub struct Spectral<T = f32, const N: usize = 2> {
+    fft: Fft<T>,
+    window: Vec<T>,
+    channels: [SpectralChannel<T>; N],
+    output_index: usize,
+    // Samples since the start of the current hop.
+    hop_sample: usize,
+    // Next step on the current frame, over channels and stages.
+    next_step: usize,
+    // Index in the output ring of the first sample of the current frame.
+    frame_start: usize,
+    density: f32,
+    is_frozen: bool,
+    phase_randomization: f32,
+    quantization: f32,
+}
+
+impl<T: Sample, const N: usize> Clone for Spectral<T, N> {
+    fn clone(&self) -> Self {
+        Spectral {
+            fft: self.fft.clone(),
+            window: self.window.clone(),
+            channels: self.channels.clone(),
+            ..*self
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.fft.clone_from(&source.fft);
+        self.window.clone_from(&source.window);
+        self.channels.clone_from(&source.channels);
+        self.output_index = source.output_index;
+        self.hop_sample = source.hop_sample;
+        self.next_step = source.next_step;
+        self.frame_start = source.frame_start;
+        self.density = source.density;
+        self.is_frozen = source.is_frozen;
+        self.phase_randomization = source.phase_randomization;
+        self.quantization = source.quantization;
+    }
+}
+
+impl<T: Sample, const N: usize> Spectral<T, N> {
+    pub fn new(density: f32) -> Spectral<T, N> {
+        let half = T::from_f32(0.5);
+        let window = (0..FFT_SIZE)
+            .map(|i| half - half * (T::TAU() * T::from_usize(i) / T::from_usize(FFT_SIZE)).cos())
+            .collect();
+
+        Spectral {
+            fft: Fft::new(FFT_SIZE),
+            window,
+            channels: std::array::from_fn(|_| SpectralChannel::new()),
+            output_index: 0,
+            hop_sample: 0,
+            next_step: Self::NUM_STEPS,
+            frame_start: 0,
+            density,
+            is_frozen: false,
+            phase_randomization: 0.0,
+            quantization: 0.0,
+        }
+    }
+
+    // Gathering the frame, then every stage of every channel.
+    const NUM_STEPS: usize = 1 + STAGES.len() * N;
+
+    pub fn process(
+        &mut self,
+        delay_line: &DelayLine<T, N>,
+        position: T,
+        duration: T,
+        pitch: T,
+        rng: &mut impl Rng,
+    ) -> Frame<T, N> {
+        if self.hop_sample == 0 {
+            // Finishes the previous frame when a hop is too short for it.
+            while self.next_step < Self::NUM_STEPS {
+                self.process_step(duration, pitch, rng);
+            }
+            self.gather(delay_line, position);
+            self.next_step = 1;
+            self.frame_start = (self.output_index + HOP_SIZE) % OUTPUT_SIZE;
+        } else if self.next_step < Self::NUM_STEPS {
+            self.process_step(duration, pitch, rng);
+        }
+        self.hop_sample = (self.hop_sample + 1) % HOP_SIZE;
+
+        let mut frame = [T::zero(); N];
+        for (sample, channel) in frame.iter_mut().zip(self.channels.iter_mut()) {
+            *sample = channel.output[self.output_index];
+            channel.output[self.output_index] = T::zero();
+        }
+        self.output_index = (self.output_index + 1) % OUTPUT_SIZE;
+
+        frame
+    }
+
+    /**
+     * Windows the frame ending `position` samples behind the write head into
+     * the spectrum of every channel.
+     */
+    fn gather(&mut self, delay_line: &DelayLine<T, N>, position: T) {
+        for i in 0..FFT_SIZE {
+            let delay = position + T::from_usize(FFT_SIZE - 1 - i);
+            let frame = delay_line.read(delay);
+            for (channel, sample) in self.channels.iter_mut().zip(frame.iter()) {
+                channel.spectrum[i] = Complex::new(*sample * self.window[i], T::zero());
+            }
+        }
+    }
+
+    /**
+     * Runs the next stage of the current frame, step 0 being `gather`.
+     */
+    fn process_step(&mut self, duration: T, pitch: T, rng: &mut impl Rng) {
+        let step = self.next_step - 1;
+        let channel_index = step / STAGES.len();
+        let stage = STAGES[step % STAGES.len()];
+        self.next_step += 1;
+
+        let expected_phase_advance = T::TAU() * T::from_usize(HOP_SIZE) / T::from_usize(FFT_SIZE);
+        let channel = &mut self.channels[channel_index];
+        match stage {
+            Stage::Forward => self.fft.forward(&mut channel.spectrum),
+            Stage::Analyse => {
+                if self.is_frozen {
+                    return;
+                }
+                let blur = T::one() - (-T::from_usize(HOP_SIZE) / duration.max(T::one())).exp();
+                let update_probability = self.density / MAX_DENSITY;
+                for bin in 0..NUM_BINS {
+                    let magnitude = channel.spectrum[bin].magnitude();
+                    let phase = channel.spectrum[bin].phase();
+                    let deviation = wrap_phase(
+                        phase
+                            - channel.previous_phases[bin]
+                            - T::from_usize(bin) * expected_phase_advance,
+                    );
+                    channel.previous_phases[bin] = phase;
+
+                    if rng.gen::<f32>() < update_probability {
+                        let difference = magnitude - channel.magnitudes[bin];
+                        channel.magnitudes[bin] += blur * difference;
+                        channel.frequencies[bin] =
+                            T::from_usize(bin) + deviation / expected_phase_advance;
+                    }
+                }
+            }
+            Stage::Synthesize => {
+                let quantization_step =
+                    T::from_f32(1.0 + (self.quantization * (MAX_QUANTIZATION_STEP - 1.0)).round());
+                for bin in 0..NUM_BINS {
+                    channel.shifted_magnitudes[bin] = T::zero();
+                    channel.shifted_frequencies[bin] = T::from_usize(bin);
+                }
+                for bin in 0..NUM_BINS {
+                    let bin_frequency = T::from_usize(bin);
+                    let shifted =
+                        (bin_frequency * pitch / quantization_step).round() * quantization_step;
+                    if shifted < T::from_usize(NUM_BINS) {
+                        let shifted = shifted.to_index();
+                        channel.shifted_magnitudes[shifted] += channel.magnitudes[bin];
+                        channel.shifted_frequencies[shifted] = channel.frequencies[bin] * pitch
+                            + T::from_usize(shifted)
+                            - bin_frequency * pitch;
+                    }
+                }
+
+                for bin in 0..NUM_BINS {
+                    channel.synthesis_phases[bin] = wrap_phase(
+                        channel.synthesis_phases[bin]
+                            + channel.shifted_frequencies[bin] * expected_phase_advance,
+                    );
+                    let random_phase = if self.phase_randomization > 0.0 {
+                        T::from_f32(rng.gen_range(-PI..PI) * self.phase_randomization)
+                    } else {
+                        T::zero()
+                    };
+                    channel.spectrum[bin] = Complex::from_polar(
+                        channel.shifted_magnitudes[bin],
+                        channel.synthesis_phases[bin] + random_phase,
+                    );
+                }
+                for bin in NUM_BINS..FFT_SIZE {
+                    channel.spectrum[bin] = channel.spectrum[FFT_SIZE - bin].conj();
+                }
+            }
+            Stage::Inverse => self.fft.inverse(&mut channel.spectrum),
+            Stage::OverlapAdd => {
+                for i in 0..FFT_SIZE {
+                    let index = (self.frame_start + i) % OUTPUT_SIZE;
+                    channel.output[index] +=
+                        channel.spectrum[i].re * self.window[i] * T::from_f32(OVERLAP_ADD_GAIN);
+                }
+            }
+        }
+    }
+
+    /**
+     * Silences the output and forgets the analysed spectrum.
+     */
+    pub fn reset(&mut self) {
+        for channel in self.channels.iter_mut() {
+            channel.reset();
+        }
+        self.output_index = 0;
+        self.hop_sample = 0;
+        self.next_step = Self::NUM_STEPS;
+        self.frame_start = 0;
+    }
+
+    pub fn set_density(&mut self, density: f32) {
+        self.density = density;
+    }
+
+    pub fn set_freeze(&mut self, is_frozen: bool) {
+        self.is_frozen = is_frozen;
+    }
+
+    pub fn set_phase_randomization(&mut self, phase_randomization: f32) {
+        self.phase_randomization = phase_randomization;
+    }
+
+    pub fn set_quantization(&mut self, quantization: f32) {
+        self.quantization = quantization;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::delay_line::DelayLine;
+    use std::f32::consts::PI;
+
+    fn sine_delay_line(frequency_bins: f32, length: usize) -> DelayLine {
+        let mut delay_line: DelayLine = DelayLine::new(length);
+        for i in 0..length {
+            let sample = (2.0 * PI * frequency_bins * i as f32 / super::FFT_SIZE as f32).sin();
+            delay_line.write_and_advance([sample, sample]);
+        }
+        delay_line
+    }
+
+    #[test]
+    fn resynthesizes_delay_content() {
+        let delay_line = sine_delay_line(64.0, 3 * super::FFT_SIZE);
+        let mut spectral: super::Spectral = super::Spectral::new(super::MAX_DENSITY);
+        let mut rng = rand::thread_rng();
+
+        let mut peak: f32 = 0.0;
+        for _ in 0..2 * super::FFT_SIZE {
+            let [left, right] = spectral.process(&delay_line, 0.0, 1.0, 1.0, &mut rng);
+            assert_eq!(left, right);
+            peak = peak.max(left.abs());
+        }
+        assert!(peak > 0.5 && peak < 1.5);
+    }
+
+    #[test]
+    fn zero_density_never_updates_spectrum() {
+        let delay_line = sine_delay_line(64.0, 3 * super::FFT_SIZE);
+        let mut spectral: super::Spectral = super::Spectral::new(0.0);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..super::FFT_SIZE {
+            assert_eq!(
+                spectral.process(&delay_line, 0.0, 1.0, 1.0, &mut rng),
+                [0.0, 0.0]
+            );
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// Conversions between the engine's units, samples and playback rates,
+// and musical units.
+
+pub fn milliseconds_to_samples(milliseconds: f32, sample_rate: usize) -> f32 {
+    milliseconds * sample_rate as f32 / 1000.0
+}
+
+pub fn samples_to_milliseconds(samples: f32, sample_rate: usize) -> f32 {
+    samples * 1000.0 / sample_rate as f32
+}
+
+/**
+ * Playback rate of a transposition, 12.0 semitones double the rate.
+ */
+pub fn semitones_to_ratio(semitones: f32) -> f32 {
+    (semitones / 12.0).exp2()
+}
+
+pub fn ratio_to_semitones(ratio: f32) -> f32 {
+    12.0 * ratio.log2()
+}
+
+/**
+ * 1 V/oct: 0.0 V plays at the original pitch, every volt adds an octave.
+ */
+pub fn volts_to_ratio(volts: f32) -> f32 {
+    volts.exp2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_round_trip() {
+        assert_eq!(milliseconds_to_samples(500.0, 48000), 24000.0);
+        assert_eq!(samples_to_milliseconds(24000.0, 48000), 500.0);
+    }
+
+    #[test]
+    fn pitch_conversions_agree() {
+        assert!((semitones_to_ratio(12.0) - 2.0).abs() < 1e-6);
+        assert!((semitones_to_ratio(-7.0) - volts_to_ratio(-7.0 / 12.0)).abs() < 1e-6);
+        assert!((ratio_to_semitones(semitones_to_ratio(3.5)) - 3.5).abs() < 1e-5);
+        assert_eq!(volts_to_ratio(0.0), 1.0);
+    }
+}